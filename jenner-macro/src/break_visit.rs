@@ -1,7 +1,11 @@
+use proc_macro2::Ident;
+use quote::{format_ident, ToTokens};
 use syn::{
     parse_quote,
-    visit_mut::{visit_expr_break_mut, visit_expr_mut, visit_item_mut, VisitMut},
-    Label,
+    visit_mut::{
+        visit_expr_break_mut, visit_expr_mut, visit_expr_return_mut, visit_item_mut, VisitMut,
+    },
+    Expr, ExprBlock, ExprForLoop, ExprLoop, ExprWhile, Label, Lifetime,
 };
 
 pub struct BreakVisitor<'f> {
@@ -61,3 +65,252 @@ impl<'f> VisitMut for BreakVisitor<'f> {
         }
     }
 }
+
+/// Turns the `break` and `continue` of a `concurrent` for-loop body into returns
+/// from the generator that runs it, as `ControlFlow::Break` and `ControlFlow::Continue`
+pub struct LoopExitVisitor<'f> {
+    pub label: &'f Option<Label>,
+    pub fallible: bool,
+    /// The labels of the loops and labelled blocks inside the body,
+    /// and whether each is a loop that an unlabelled `break` would exit
+    pub inner: Vec<(Option<Lifetime>, bool)>,
+    pub error: Option<syn::Error>,
+}
+
+impl LoopExitVisitor<'_> {
+    /// Whether a `break` or `continue` with this label exits the body,
+    /// recording an error if it exits further than that
+    fn exits(&mut self, label: &Option<Lifetime>, tokens: impl ToTokens) -> bool {
+        match label {
+            None => !self.inner.iter().any(|(_, is_loop)| *is_loop),
+            Some(label)
+                if self
+                    .inner
+                    .iter()
+                    .any(|(inner, _)| inner.as_ref() == Some(label)) =>
+            {
+                false
+            }
+            Some(label)
+                if self
+                    .label
+                    .as_ref()
+                    .is_some_and(|outer| outer.name == *label) =>
+            {
+                true
+            }
+            Some(_) => {
+                self.error(
+                    tokens,
+                    "cannot exit a loop outside of a `concurrent` for-loop body",
+                );
+                false
+            }
+        }
+    }
+
+    fn error(&mut self, tokens: impl ToTokens, message: &str) {
+        let error = syn::Error::new_spanned(tokens, message);
+        match &mut self.error {
+            Some(errors) => errors.combine(error),
+            None => self.error = Some(error),
+        }
+    }
+
+    fn exit(&self, flow: Expr) -> Expr {
+        if self.fallible {
+            parse_quote! { return ::core::result::Result::Ok(#flow) }
+        } else {
+            parse_quote! { return #flow }
+        }
+    }
+}
+
+impl VisitMut for LoopExitVisitor<'_> {
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        match i {
+            // don't propagate search through closures or async blocks
+            Expr::Closure(_) | Expr::Async(_) => {}
+
+            Expr::ForLoop(ExprForLoop { label, .. })
+            | Expr::Loop(ExprLoop { label, .. })
+            | Expr::While(ExprWhile { label, .. }) => {
+                self.inner
+                    .push((label.as_ref().map(|label| label.name.clone()), true));
+                visit_expr_mut(self, i);
+                self.inner.pop();
+            }
+            Expr::Block(ExprBlock {
+                label: Some(label), ..
+            }) => {
+                self.inner.push((Some(label.name.clone()), false));
+                visit_expr_mut(self, i);
+                self.inner.pop();
+            }
+
+            Expr::Break(break_) => {
+                visit_expr_break_mut(self, break_);
+                if self.exits(&break_.label, &*break_) {
+                    if let Some(expr) = &break_.expr {
+                        self.error(expr, "a `concurrent` for-loop cannot break with a value");
+                    }
+                    *i = self.exit(parse_quote! { ::core::ops::ControlFlow::Break(()) });
+                }
+            }
+            Expr::Continue(continue_) => {
+                if self.exits(&continue_.label, &*continue_) {
+                    *i = self.exit(parse_quote! { ::core::ops::ControlFlow::Continue(()) });
+                }
+            }
+            Expr::Return(return_) => {
+                self.error(
+                    &*return_,
+                    "`return` is not supported in `concurrent` for-loop bodies",
+                );
+            }
+
+            // propagate as normal
+            i => visit_expr_mut(self, i),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+/// Gives the unlabelled `break` and `continue` after an `on_cancel!` the label of the loop they exit,
+/// as they would otherwise exit the labelled block that the rest of the scope is lowered into
+pub struct CancelScopeExits<'a> {
+    pub label: &'a Lifetime,
+    /// How many loops inside the scope enclose the current expression
+    pub inner: usize,
+    pub used: bool,
+}
+
+impl VisitMut for CancelScopeExits<'_> {
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        match i {
+            // don't propagate search through closures or async blocks
+            Expr::Closure(_) | Expr::Async(_) => {}
+
+            Expr::ForLoop(_) | Expr::Loop(_) | Expr::While(_) => {
+                self.inner += 1;
+                visit_expr_mut(self, i);
+                self.inner -= 1;
+            }
+
+            Expr::Break(break_) => {
+                visit_expr_break_mut(self, break_);
+                if break_.label.is_none() && self.inner == 0 {
+                    break_.label = Some(self.label.clone());
+                    self.used = true;
+                }
+            }
+            Expr::Continue(continue_) => {
+                if continue_.label.is_none() && self.inner == 0 {
+                    continue_.label = Some(self.label.clone());
+                    self.used = true;
+                }
+            }
+
+            // propagate as normal
+            i => visit_expr_mut(self, i),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+/// Turns the `return`s of a `scope!` body, and the `break`s and `continue`s that exit it,
+/// into breaks out of the labelled block that the body is lowered into,
+/// so the children can be joined before the exit is taken
+pub struct ScopeExits {
+    pub label: Lifetime,
+    /// The enum with a variant for each exit, carrying its value
+    pub exit_ty: Ident,
+    /// The labels of the loops and labelled blocks inside the body,
+    /// and whether each is a loop that an unlabelled `break` would exit
+    pub inner: Vec<(Option<Lifetime>, bool)>,
+    /// The exits out of the body, in the order of their variants, taking their value from `__exit__`
+    pub exits: Vec<Expr>,
+}
+
+impl ScopeExits {
+    /// Whether a `break` or `continue` with this label exits the body
+    fn exits(&self, label: &Option<Lifetime>) -> bool {
+        match label {
+            None => !self.inner.iter().any(|(_, is_loop)| *is_loop),
+            Some(label) => !self
+                .inner
+                .iter()
+                .any(|(inner, _)| inner.as_ref() == Some(label)),
+        }
+    }
+
+    /// Breaks out of the body with the value of the exit, recording the exit to take afterwards
+    fn exit(&mut self, value: Option<Box<Expr>>, exit: Expr) -> Expr {
+        let variant = format_ident!("Exit{}", self.exits.len());
+        self.exits.push(exit);
+        let value = value.map_or_else(|| parse_quote! { () }, |value| *value);
+        let Self { label, exit_ty, .. } = self;
+        // parenthesised, or the label and the leading `::` parse as a labelled expression
+        parse_quote! {
+            break #label (::core::ops::ControlFlow::Break(#exit_ty::#variant(#value)))
+        }
+    }
+}
+
+impl VisitMut for ScopeExits {
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        match i {
+            // don't propagate search through closures or async blocks
+            Expr::Closure(_) | Expr::Async(_) => {}
+
+            Expr::ForLoop(ExprForLoop { label, .. })
+            | Expr::Loop(ExprLoop { label, .. })
+            | Expr::While(ExprWhile { label, .. }) => {
+                self.inner
+                    .push((label.as_ref().map(|label| label.name.clone()), true));
+                visit_expr_mut(self, i);
+                self.inner.pop();
+            }
+            Expr::Block(ExprBlock {
+                label: Some(label), ..
+            }) => {
+                self.inner.push((Some(label.name.clone()), false));
+                visit_expr_mut(self, i);
+                self.inner.pop();
+            }
+
+            Expr::Break(break_) => {
+                visit_expr_break_mut(self, break_);
+                if self.exits(&break_.label) {
+                    let label = &break_.label;
+                    let exit = match break_.expr {
+                        Some(_) => parse_quote! { break #label __exit__ },
+                        None => parse_quote! { break #label },
+                    };
+                    *i = self.exit(break_.expr.take(), exit);
+                }
+            }
+            Expr::Continue(continue_) => {
+                if self.exits(&continue_.label) {
+                    let exit = Expr::Continue(continue_.clone());
+                    *i = self.exit(None, exit);
+                }
+            }
+            Expr::Return(return_) => {
+                visit_expr_return_mut(self, return_);
+                let exit = match return_.expr {
+                    Some(_) => parse_quote! { return __exit__ },
+                    None => parse_quote! { return },
+                };
+                *i = self.exit(return_.expr.take(), exit);
+            }
+
+            // propagate as normal
+            i => visit_expr_mut(self, i),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
@@ -0,0 +1,162 @@
+use proc_macro2::Ident;
+use quote::format_ident;
+use syn::{
+    parse_quote,
+    visit_mut::{visit_expr_mut, visit_item_mut, VisitMut},
+    Block, Expr, ExprReturn, Stmt, Type,
+};
+
+use crate::gen_visit::is_macro;
+
+/// Wraps every effect returned from a `dispatch` function in a variant of a generated enum,
+/// so that branches returning different effect types unify into a single type.
+/// Each effect is lifted into the failure and async types of the function first,
+/// and into a stream if the function yields.
+pub struct DispatchVisitor {
+    pub variants: Vec<Ident>,
+    pub yields: bool,
+    pub failure: Type,
+    pub async_: Type,
+}
+
+impl DispatchVisitor {
+    pub fn new(yields: bool, failure: Type, async_: Type) -> Self {
+        Self {
+            variants: vec![],
+            yields,
+            failure,
+            async_,
+        }
+    }
+
+    pub fn into_dispatch(mut self, block: &mut Block) -> Vec<Stmt> {
+        self.visit_block_mut(block);
+        self.dispatch_block(block);
+
+        let variants = &self.variants;
+        let Some(first) = variants.first() else {
+            // every branch diverges
+            return block.stmts.clone();
+        };
+        let rest = &variants[1..];
+        let stmts = &block.stmts;
+        parse_quote! {
+            ::jenner::__private::pin_project! {
+                #[project = __DispatchProj]
+                enum __Dispatch<#(#variants),*> {
+                    #(#variants { #[pin] effect: #variants },)*
+                }
+            }
+
+            impl<#(#variants),*> ::jenner::effective::Effective for __Dispatch<#(#variants),*>
+            where
+                #first: ::jenner::effective::Effective,
+                #(#rest: ::jenner::effective::Effective<
+                    Item = <#first as ::jenner::effective::Effective>::Item,
+                    Failure = <#first as ::jenner::effective::Effective>::Failure,
+                    Produces = <#first as ::jenner::effective::Effective>::Produces,
+                    Async = <#first as ::jenner::effective::Effective>::Async,
+                >,)*
+            {
+                type Item = <#first as ::jenner::effective::Effective>::Item;
+                type Failure = <#first as ::jenner::effective::Effective>::Failure;
+                type Produces = <#first as ::jenner::effective::Effective>::Produces;
+                type Async = <#first as ::jenner::effective::Effective>::Async;
+
+                fn poll_effect(
+                    self: ::jenner::__private::pin::Pin<&mut Self>,
+                    cx: &mut ::jenner::__private::task::Context<'_>,
+                ) -> ::jenner::effective::EffectiveResult<Self> {
+                    match self.project() {
+                        #(__DispatchProj::#variants { effect } => {
+                            ::jenner::effective::Effective::poll_effect(effect, cx)
+                        })*
+                    }
+                }
+            }
+
+            #(#stmts)*
+        }
+    }
+
+    /// Wraps the tail expression of a block
+    fn dispatch_block(&mut self, block: &mut Block) {
+        if let Some(Stmt::Expr(expr, None)) = block.stmts.last_mut() {
+            self.dispatch_expr(expr);
+        }
+    }
+
+    /// Wraps each branch of an expression in tail position
+    fn dispatch_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::If(if_) => {
+                self.dispatch_block(&mut if_.then_branch);
+                if let Some((_, else_)) = &mut if_.else_branch {
+                    self.dispatch_expr(else_);
+                }
+            }
+            Expr::Match(match_) => {
+                for arm in &mut match_.arms {
+                    self.dispatch_expr(&mut arm.body);
+                }
+            }
+            Expr::Block(block) if block.label.is_none() => self.dispatch_block(&mut block.block),
+            Expr::Unsafe(block) => self.dispatch_block(&mut block.block),
+            Expr::Paren(paren) => self.dispatch_expr(&mut paren.expr),
+
+            // diverging expressions don't need wrapping
+            Expr::Return(_) | Expr::Break(_) | Expr::Continue(_) => {}
+            Expr::Macro(mac)
+                if ["panic", "todo", "unimplemented", "unreachable"]
+                    .iter()
+                    .any(|name| is_macro(&mac.mac, name)) => {}
+
+            expr => {
+                let variant = format_ident!("__E{}", self.variants.len());
+                let (failure, async_) = (&self.failure, &self.async_);
+                if self.yields {
+                    *expr = parse_quote! { ::jenner::__private::Delegate::new(#expr) };
+                }
+                *expr = parse_quote! {
+                    __Dispatch::#variant {
+                        effect: ::jenner::__private::Lift::<_, #failure, #async_>::new(#expr),
+                    }
+                };
+                self.variants.push(variant);
+            }
+        }
+    }
+}
+
+impl VisitMut for DispatchVisitor {
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        match i {
+            // don't propagate search through closures or async blocks
+            Expr::Closure(_) | Expr::Async(_) => {}
+
+            Expr::Return(ExprReturn {
+                expr: Some(expr), ..
+            }) => {
+                self.visit_expr_mut(expr);
+                self.dispatch_expr(expr);
+            }
+
+            // propagate as normal
+            i => visit_expr_mut(self, i),
+        }
+    }
+
+    fn visit_item_mut(&mut self, i: &mut syn::Item) {
+        match i {
+            syn::Item::Fn(_)
+            | syn::Item::ForeignMod(_)
+            | syn::Item::Impl(_)
+            | syn::Item::Macro(_)
+            | syn::Item::Mod(_)
+            | syn::Item::Trait(_) => {}
+
+            // propagate as normal
+            i => visit_item_mut(self, i),
+        }
+    }
+}
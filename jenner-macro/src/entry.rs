@@ -0,0 +1,100 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote, Error, GenericArgument, ItemFn, PathArguments, Result, ReturnType, Type, TypePath,
+    Visibility,
+};
+
+use crate::{parse::AttrGenerator, process::create_fallible_return_type};
+
+/// An `#[effect]` function run by `#[jenner::test]` or `#[jenner::main]`
+pub struct Entry {
+    pub generator: AttrGenerator,
+    /// Whether this is a test rather than the program's entry point
+    pub test: bool,
+    /// `timeout = ms`, how long the effect can be pending before it is reported as a failure
+    pub timeout: Option<u64>,
+}
+
+impl Entry {
+    /// Expands to a plain function that drives the effect, declared inside it, to completion
+    pub fn expand(self) -> Result<TokenStream> {
+        let Entry {
+            generator,
+            test,
+            timeout,
+        } = self;
+        let ItemFn {
+            attrs, vis, sig, ..
+        } = generator.func.clone();
+        let ident = &sig.ident;
+
+        if let Some(arg) = sig.inputs.first() {
+            return Err(Error::new_spanned(
+                arg,
+                "entry point functions cannot take arguments",
+            ));
+        }
+        if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+            return Err(Error::new_spanned(
+                &sig.generics,
+                "entry point functions cannot be generic",
+            ));
+        }
+
+        let output: Type = match sig.output {
+            ReturnType::Default => parse_quote! { () },
+            ReturnType::Type(_, ty) if generator.fallible => {
+                result_ok_type(&ty).unwrap_or_else(|| create_fallible_return_type(*ty))
+            }
+            ReturnType::Type(_, ty) => *ty,
+        };
+
+        let mut body = generator.process()?;
+        body.attrs.clear();
+        body.vis = Visibility::Inherited;
+
+        let timeout = match timeout {
+            Some(ms) => {
+                quote! { ::core::option::Option::Some(::core::time::Duration::from_millis(#ms)) }
+            }
+            None => quote! { ::core::option::Option::None },
+        };
+        let (test_attr, run) = if test {
+            (
+                quote! { #[::core::prelude::v1::test] },
+                quote! { ::jenner::__private::run_test },
+            )
+        } else {
+            (quote! {}, quote! { ::jenner::__private::run_main })
+        };
+
+        Ok(quote! {
+            #test_attr
+            #(#attrs)*
+            #vis fn #ident() -> #output {
+                #body
+                #run(#ident(), #timeout)
+            }
+        })
+    }
+}
+
+/// The `T` of a `Result<T, E>` return type.
+/// `#[test]` needs it spelled out to accept `#[should_panic]`, which the `SimpleTry` projection is not
+fn result_ok_type(ty: &Type) -> Option<Type> {
+    let Type::Path(TypePath { qself: None, path }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    }
+}
@@ -1,5 +1,7 @@
-use proc_macro2::Ident;
-use quote::format_ident;
+use std::mem;
+
+use proc_macro2::{Ident, Span};
+use quote::{format_ident, quote};
 use rand::{distributions::Alphanumeric, Rng};
 use syn::{
     ext::IdentExt,
@@ -9,19 +11,61 @@ use syn::{
     token::{self, Comma},
     visit_mut::{
         visit_expr_for_loop_mut, visit_expr_method_call_mut, visit_expr_mut, visit_expr_yield_mut,
-        VisitMut,
+        visit_local_mut, visit_pat_ident_mut, visit_stmt_mut, VisitMut,
     },
-    Expr, ExprAssign, ExprAwait, ExprCall, ExprForLoop, ExprMethodCall, ExprPath, ExprTry,
-    ExprTuple, ExprYield, Stmt, Type,
+    Attribute, Block, Expr, ExprAssign, ExprAwait, ExprCall, ExprClosure, ExprForLoop, ExprLoop,
+    ExprMacro, ExprMethodCall, ExprPath, ExprTry, ExprTuple, ExprWhile, ExprYield, Label, Lifetime,
+    Local, Macro, Pat, PatIdent, Path, Stmt, Token, Type,
 };
 
-use crate::break_visit::BreakVisitor;
+use crate::{
+    break_visit::{BreakVisitor, CancelScopeExits, LoopExitVisitor, ScopeExits},
+    parse::SelectInput,
+};
 
 pub struct GenVisitor {
     pub cx: Ident,
     pub sync: bool,
+    /// The blocking variant of a `maybe_async` body,
+    /// where awaits and for-loops park the thread while their effect is pending
+    pub park: bool,
     pub yields: bool,
     pub fallible: bool,
+    /// The resume and throw types of a `resumes(T)` generator
+    pub resumes: Option<(Type, Type)>,
+    pub lending: bool,
+    /// Emit a movable (non-static) generator
+    pub movable: bool,
+    /// Keep the concrete effect type so the generator can be cloned
+    pub clone: bool,
+    /// The item enum of a multi-output generator, `yield name(x)` yields `Enum::name(x)`
+    pub outputs: Option<Ident>,
+    /// Yields are wrapped in `Ok`, and `yield_err!(e)` yields `Err(e)` without completing
+    pub recoverable: bool,
+    /// The operations of a `uses(...)` generator, which suspends with `perform!(op)`
+    pub uses: Option<Type>,
+    /// Pending awaits check for cancellation when resumed
+    pub cancellable: bool,
+    /// The label of the innermost `on_cancel!` scope, which a cancelled await breaks out of
+    /// to run its cleanup
+    cancel_scope: Option<Lifetime>,
+    /// How many `on_cancel!` scopes have been lowered, to give each a unique label
+    cancel_scopes: usize,
+    /// The labels of the loops enclosing the current expression in a cancellable body,
+    /// and whether an `on_cancel!` scope exits the loop by its label
+    loops: Vec<(Lifetime, bool)>,
+    /// How many loops have been given a label of their own
+    loop_labels: usize,
+    /// How many steps the generator takes without suspending before it yields to the executor
+    pub budget: Option<u32>,
+    /// The enclosing `jenner::scope!` calls, whose children are polled whenever the body
+    /// is pending or resumed
+    scopes: Vec<Ident>,
+    /// Set while lowering a `yield_err!`, so the yield is not wrapped in `Ok`
+    yield_err: bool,
+    /// Set while lowering the initializer of a `let`,
+    /// where an `#[effect(async)]` for-loop evaluates to the final value of its generator
+    loop_output: bool,
 }
 
 impl GenVisitor {
@@ -34,86 +78,657 @@ impl GenVisitor {
         GenVisitor {
             cx: format_ident!("__cx_{}", random),
             sync,
+            park: false,
             yields,
             fallible,
+            resumes: None,
+            lending: false,
+            movable: false,
+            clone: false,
+            outputs: None,
+            recoverable: false,
+            uses: None,
+            cancellable: false,
+            cancel_scope: None,
+            cancel_scopes: 0,
+            loops: vec![],
+            loop_labels: 0,
+            budget: None,
+            scopes: vec![],
+            yield_err: false,
+            loop_output: false,
         }
     }
 
-    pub fn into_generator(mut self, stmts: &mut [Stmt]) -> Expr {
-        if !self.sync {
-            stmts.iter_mut().for_each(|stmt| self.visit_stmt_mut(stmt));
-        }
+    pub fn into_generator(mut self, stmts: &mut Vec<Stmt>) -> Expr {
+        self.visit_stmts(stmts);
 
         let Self {
             cx,
             yields,
             sync,
+            park: _,
             fallible,
+            resumes,
+            lending,
+            movable,
+            clone,
+            outputs: _,
+            recoverable: _,
+            uses,
+            cancellable,
+            cancel_scope: _,
+            cancel_scopes: _,
+            loops: _,
+            loop_labels: _,
+            budget,
+            scopes: _,
+            yield_err: _,
+            loop_output: _,
         } = self;
 
+        // the generator outlives the call, so it takes ownership of the arguments,
+        // including those the body only uses by reference
+        let static_: Option<Token![static]> = (!movable).then(Default::default);
+        let budget = budget.map(|steps| {
+            quote! { let mut __budget__ = ::jenner::__private::Budget::new(#steps); }
+        });
+
+        if let Some(ops) = uses {
+            let (mode, yield_ty) = generator_mode(yields, fallible);
+            let resume: Type = parse_quote! {
+                ::jenner::__private::PerformContext<<#ops as ::jenner::Operations>::Response>
+            };
+            let body = if fallible {
+                quote! { Ok({ #(#stmts)* }) }
+            } else {
+                quote! { #(#stmts)* }
+            };
+            return if sync {
+                parse_quote! {
+                    ::jenner::__private::UsesImpl::<_, #ops, #mode, ::jenner::effective::Blocking>::create::<#yield_ty, _>(
+                        #static_ move |_: #resume| { #body }
+                    )
+                }
+            } else {
+                parse_quote! {
+                    ::jenner::__private::UsesImpl::<_, #ops, #mode, ::jenner::effective::Async>::create::<#yield_ty, _>(
+                        #static_ move |__resume__: #resume| {
+                            let mut #cx = __resume__.into_context();
+                            #body
+                        }
+                    )
+                }
+            };
+        }
+
+        if cancellable {
+            let (mode, yield_ty) = generator_mode(yields, fallible);
+            let body = if fallible {
+                quote! { Ok({ #(#stmts)* }) }
+            } else {
+                quote! { #(#stmts)* }
+            };
+            return parse_quote! {
+                ::jenner::__private::CancellableImpl::<_, #mode>::create::<#yield_ty, _>(
+                    #static_ move |__resume__: ::jenner::__private::CancelContext| {
+                        let mut #cx = __resume__.into_context();
+                        #body
+                    }
+                )
+            };
+        }
+
+        if lending {
+            return match (sync, fallible) {
+                (true, false) => parse_quote! {
+                    ::jenner::__private::SyncLendingGeneratorImpl::create(#static_ move || { #(#stmts)* })
+                },
+                (true, true) => parse_quote! {
+                    ::jenner::__private::SyncFallibleLendingGeneratorImpl::create(
+                        #static_ move || Ok({ #(#stmts)* })
+                    )
+                },
+                (false, false) => parse_quote! {
+                    ::jenner::__private::AsyncLendingGeneratorImpl::create(
+                        #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| { #(#stmts)* }
+                    )
+                },
+                (false, true) => parse_quote! {
+                    ::jenner::__private::AsyncFallibleLendingGeneratorImpl::create(
+                        #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| Ok({ #(#stmts)* })
+                    )
+                },
+            };
+        }
+
+        if let Some((resume_ty, throw_ty)) = resumes {
+            return match (sync, fallible) {
+                (true, false) => parse_quote! {
+                    ::jenner::__private::SyncResumeGeneratorImpl::create(
+                        #static_ move |_: ::core::option::Option<::core::result::Result<#resume_ty, #throw_ty>>| {
+                            #(#stmts)*
+                        }
+                    )
+                },
+                (true, true) => parse_quote! {
+                    ::jenner::__private::SyncFallibleResumeGeneratorImpl::create(
+                        #static_ move |_: ::core::option::Option<::core::result::Result<#resume_ty, #throw_ty>>| {
+                            Ok({ #(#stmts)* })
+                        }
+                    )
+                },
+                (false, false) => parse_quote! {
+                    ::jenner::__private::AsyncResumeGeneratorImpl::create(
+                        #static_ move |__resume__: ::jenner::__private::ResumeContext<#resume_ty, #throw_ty>| {
+                            let mut #cx = __resume__.into_context();
+                            #(#stmts)*
+                        }
+                    )
+                },
+                (false, true) => parse_quote! {
+                    ::jenner::__private::AsyncFallibleResumeGeneratorImpl::create(
+                        #static_ move |__resume__: ::jenner::__private::ResumeContext<#resume_ty, #throw_ty>| {
+                            let mut #cx = __resume__.into_context();
+                            Ok({ #(#stmts)* })
+                        }
+                    )
+                },
+            };
+        }
+
+        let create = if clone {
+            format_ident!("new")
+        } else {
+            format_ident!("create")
+        };
         match (sync, yields, fallible) {
             (true, true, false) => parse_quote! {
-                ::jenner::__private::SyncGeneratorImpl::create(static || { #(#stmts)* })
+                ::jenner::__private::SyncGeneratorImpl::#create(#static_ move || { #(#stmts)* })
             },
             (true, false, false) => parse_quote! {
-                ::jenner::effective::wrappers::from_fn_once(|| { #(#stmts)* })
+                ::jenner::effective::wrappers::from_fn_once(move || { #(#stmts)* })
             },
             (false, true, false) => parse_quote! {
-                ::jenner::__private::AsyncGeneratorImpl::create(
-                    static |mut #cx: ::jenner::__private::UnsafeContextRef| { #(#stmts)* }
+                ::jenner::__private::AsyncGeneratorImpl::#create(
+                    #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| { #budget #(#stmts)* }
                 )
             },
             (false, false, false) => parse_quote! {
-                ::jenner::__private::AsyncImpl::create(
-                    static |mut #cx: ::jenner::__private::UnsafeContextRef| { #(#stmts)* }
+                ::jenner::__private::AsyncImpl::#create(
+                    #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| { #(#stmts)* }
                 )
             },
             (true, true, true) => parse_quote! {
-                ::jenner::__private::SyncFallibleGeneratorImpl::create(static || Ok({ #(#stmts)* }))
+                ::jenner::__private::SyncFallibleGeneratorImpl::#create(#static_ move || Ok({ #(#stmts)* }))
             },
             (true, false, true) => parse_quote! {
                 ::jenner::effective::wrappers::fallible((|| Ok({ #(#stmts)* }))())
             },
             (false, true, true) => parse_quote! {
-                ::jenner::__private::AsyncFallibleGeneratorImpl::create(
-                    static |mut #cx: ::jenner::__private::UnsafeContextRef| Ok({ #(#stmts)* })
+                ::jenner::__private::AsyncFallibleGeneratorImpl::#create(
+                    #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| {
+                        #budget
+                        Ok({ #(#stmts)* })
+                    }
                 )
             },
             (false, false, true) => parse_quote! {
-                ::jenner::__private::AsyncFallibleImpl::create(
-                    static |mut #cx: ::jenner::__private::UnsafeContextRef| Ok({ #(#stmts)* })
+                ::jenner::__private::AsyncFallibleImpl::#create(
+                    #static_ move |mut #cx: ::jenner::__private::UnsafeContextRef| Ok({ #(#stmts)* })
                 )
             },
         }
     }
 }
 
+/// The mode marker and yield type of a generator that completes through `Mode`
+fn generator_mode(yields: bool, fallible: bool) -> (Path, Type) {
+    match (yields, fallible) {
+        (true, false) => (new_path!(::jenner::__private::Stream), parse_quote!(_)),
+        (true, true) => (
+            new_path!(::jenner::__private::FallibleStream),
+            parse_quote!(_),
+        ),
+        (false, false) => (
+            new_path!(::jenner::__private::Task),
+            parse_quote!(::core::convert::Infallible),
+        ),
+        (false, true) => (
+            new_path!(::jenner::__private::FallibleTask),
+            parse_quote!(::core::convert::Infallible),
+        ),
+    }
+}
+
+/// `yield name(x)` becomes `yield Enum::name(x)`.
+/// This stands in for `yield name <- x`, which rustc rejects before the macro sees it.
+/// `name` must be a declared output, so the call form is not ambiguous.
+fn route_output(outputs: &Ident, expr: &Expr) -> Expr {
+    if let Expr::Call(ExprCall { func, args, .. }) = expr {
+        if let (Expr::Path(ExprPath { path, .. }), 1) = (&**func, args.len()) {
+            if let Some(name) = path.get_ident() {
+                return parse_quote! { #outputs::#name(#args) };
+            }
+        }
+    }
+    let error = syn::Error::new_spanned(
+        expr,
+        "multi-output generators must name the output, eg `yield name(value)`",
+    )
+    .into_compile_error();
+    parse_quote! { #error }
+}
+
+/// Whether the macro is `name!`, eg `jenner::perform!`
+pub fn is_macro(mac: &Macro, name: &str) -> bool {
+    mac.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == name)
+}
+
+/// Whether an awaited expression is `join!(...)` or `try_join!(...)`,
+/// returning the macro and whether it short-circuits on failures
+fn join_macro(expr: &Expr) -> Option<(&Macro, bool)> {
+    match expr {
+        Expr::Macro(ExprMacro { mac, .. }) if is_macro(mac, "join") => Some((mac, false)),
+        Expr::Macro(ExprMacro { mac, .. }) if is_macro(mac, "try_join") => Some((mac, true)),
+        _ => None,
+    }
+}
+
+/// The effects a for-loop is marked with, eg `#[effect(async, concurrent(4))]`
+#[derive(Default)]
+struct ForEffects {
+    async_: bool,
+    lending: bool,
+    maybe_async: bool,
+    /// How many bodies can run at once
+    concurrent: Option<Expr>,
+    /// Concurrent bodies pass on their yields as they happen, rather than in the order of their items
+    unordered: bool,
+}
+
+/// Removes the attributes from a for-loop, parsing the effects it is marked with
+fn take_for_effects(attrs: &mut Vec<Attribute>) -> syn::Result<ForEffects> {
+    fn parser(
+        input: syn::parse::ParseStream,
+    ) -> syn::Result<Punctuated<(Ident, Option<Expr>), Comma>> {
+        Punctuated::parse_terminated_with(input, |input| {
+            let effect = Ident::parse_any(input)?;
+            let arg = if input.peek(token::Paren) {
+                let content;
+                syn::parenthesized!(content in input);
+                Some(content.parse()?)
+            } else {
+                None
+            };
+            Ok((effect, arg))
+        })
+    }
+
+    let mut effects = ForEffects::default();
+    for attr in attrs.drain(..) {
+        if !attr.path().is_ident("effect") {
+            continue;
+        }
+        let list = match attr.meta {
+            syn::Meta::List(list) => list,
+            syn::Meta::Path(_) | syn::Meta::NameValue(_) => continue,
+        };
+        for (effect, arg) in parser.parse2(list.tokens)? {
+            match (effect.to_string().as_str(), arg) {
+                ("async", None) => effects.async_ = true,
+                ("lending", None) => effects.lending = true,
+                ("maybe_async", None) => effects.maybe_async = true,
+                ("concurrent", Some(limit)) => effects.concurrent = Some(limit),
+                ("unordered", None) => effects.unordered = true,
+                ("concurrent", None) => {
+                    return Err(syn::Error::new_spanned(
+                        &effect,
+                        "`concurrent` needs the number of bodies to run at once, eg `concurrent(4)`",
+                    ))
+                }
+                ("async" | "lending" | "maybe_async" | "unordered", Some(arg)) => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        format!("`{effect}` does not take an argument"),
+                    ))
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &effect,
+                        "unknown for-loop effect, expected `async`, `lending`, `maybe_async`, `concurrent(n)` or `unordered`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(effects)
+}
+
+/// Parses the effects listed in an `#[effect(...)]` attribute
+fn parse_effects(attr: &Attribute) -> syn::Result<Vec<Ident>> {
+    if !attr.path().is_ident("effect") {
+        return Ok(vec![]);
+    }
+    match &attr.meta {
+        syn::Meta::List(list) => {
+            fn parser(input: syn::parse::ParseStream) -> syn::Result<Punctuated<Ident, Comma>> {
+                Punctuated::<Ident, Comma>::parse_terminated_with(input, Ident::parse_any)
+            }
+            Ok(parser.parse2(list.tokens.clone())?.into_iter().collect())
+        }
+        syn::Meta::Path(_) | syn::Meta::NameValue(_) => Ok(vec![]),
+    }
+}
+
+/// Converts an awaited value into an effect, whether it is an effect or an `IntoFuture`
+fn await_conversion(expr: &Expr) -> Expr {
+    parse_quote! {{
+        // weak form of specialisation.
+        #[allow(unused_imports)]
+        use ::jenner::__private::{AwaitEffective, AwaitFuture};
+        (&mut ::jenner::__private::Await::new(#expr)).into_effect()
+    }}
+}
+
+/// Converts the value a for-loop iterates over into an effect,
+/// whether it is an effect, an iterator, a stream or an async iterator
+fn for_conversion(expr: &Expr) -> Expr {
+    parse_quote! {{
+        // weak form of specialisation.
+        #[allow(unused_imports)]
+        use ::jenner::__private::{
+            IntoEffectiveAsyncIter, IntoEffectiveEffect, IntoEffectiveIter,
+            IntoEffectiveStream,
+        };
+        (&mut &mut &mut &mut ::jenner::__private::IntoEffective::new(#expr)).into_effective()
+    }}
+}
+
+/// Strips `ref` and `mut` from the bindings of a pattern, so it can match by reference
+fn ref_pattern(pat: &Pat) -> Pat {
+    struct StripBindings;
+    impl VisitMut for StripBindings {
+        fn visit_pat_ident_mut(&mut self, i: &mut PatIdent) {
+            i.by_ref = None;
+            i.mutability = None;
+            visit_pat_ident_mut(self, i);
+        }
+    }
+    let mut pat = pat.clone();
+    StripBindings.visit_pat_mut(&mut pat);
+    pat
+}
+
+/// The effects an await is marked with, eg `#[effect(maybe_async)] foo().await`
+#[derive(Default)]
+struct AwaitEffects {
+    maybe_async: bool,
+    /// The await returns `Err(Cancelled)` instead of running the `on_cancel!` blocks
+    cancellable: bool,
+}
+
+/// Removes the `#[effect(...)]` attributes from an await expression
+fn take_await_effects(await_: &mut ExprAwait) -> syn::Result<AwaitEffects> {
+    let mut effects = AwaitEffects::default();
+    for attr in mem::take(&mut await_.attrs) {
+        if !attr.path().is_ident("effect") {
+            await_.attrs.push(attr);
+            continue;
+        }
+        for effect in parse_effects(&attr)? {
+            match effect.to_string().as_str() {
+                "maybe_async" => effects.maybe_async = true,
+                "cancellable" => effects.cancellable = true,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &attr,
+                        format!(
+                        "unknown await effect `{effect}`, expected `maybe_async` or `cancellable`"
+                    ),
+                    ))
+                }
+            }
+        }
+    }
+    Ok(effects)
+}
+
+fn cancellable_await_error(await_: &ExprAwait) -> Expr {
+    let error = syn::Error::new_spanned(
+        await_.await_token,
+        "cancellable awaits require a `#[effect(cancellable)]` function",
+    )
+    .into_compile_error();
+    parse_quote! { #error }
+}
+
+/// Calls the blocking variant of a `maybe_async` function, eg `foo(x)` becomes `foo_blocking(x)`
+fn blocking_variant(expr: &mut Expr) {
+    let ident = match expr {
+        Expr::Call(ExprCall { func, .. }) => match &mut **func {
+            Expr::Path(ExprPath { path, .. }) => &mut path.segments.last_mut().unwrap().ident,
+            _ => return *expr = maybe_async_error(expr),
+        },
+        Expr::MethodCall(ExprMethodCall { method, .. }) => method,
+        _ => return *expr = maybe_async_error(expr),
+    };
+    *ident = format_ident!("{}_blocking", ident);
+}
+
+fn maybe_async_error(expr: &Expr) -> Expr {
+    let error = syn::Error::new_spanned(
+        expr,
+        "`maybe_async` effects must be a function or method call",
+    )
+    .into_compile_error();
+    parse_quote! { #error }
+}
+
 impl VisitMut for GenVisitor {
     fn visit_expr_mut(&mut self, i: &mut syn::Expr) {
         match i {
+            // closures and async blocks have their own awaits and returns
+            Expr::Closure(_) | Expr::Async(_) => {}
             Expr::Await(await_) if !self.sync => {
+                let effects = match take_await_effects(await_) {
+                    Ok(effects) => effects,
+                    Err(error) => {
+                        let error = error.into_compile_error();
+                        return *i = parse_quote! { #error };
+                    }
+                };
+                if effects.cancellable && !self.cancellable {
+                    return *i = cancellable_await_error(await_);
+                }
                 let ExprAwait { base, .. } = await_;
+                self.visit_expr_mut(base);
 
                 let cx = &self.cx;
+                let pin = self.pin_local();
+                let failure = self.lift_failure();
+                let step = self.budget_step();
+                let (ready, pending): (Expr, Stmt) = if effects.cancellable {
+                    let point = self.cancellation_point(
+                        parse_quote! { yield ::jenner::__private::CancelSuspend::Pending },
+                        parse_quote! { break ::core::result::Result::Err(::jenner::Cancelled) },
+                    );
+                    (
+                        parse_quote! { ::core::result::Result::Ok(x) },
+                        self.poll_scopes(parse_quote! { #point; }),
+                    )
+                } else {
+                    (parse_quote! { x }, self.suspend_pending())
+                };
+                if let Some((mac, short_circuit)) = join_macro(base) {
+                    let mac = mac.clone();
+                    return *i = self.join_impl(&mac, short_circuit, ready, pending);
+                }
+                let effect = await_conversion(base);
                 *i = parse_quote! {{
-                    let mut __fut__ = ::jenner::__private::pin::pin!(#base);
+                    let mut __fut__ = #pin(#effect);
                     loop {
                         let cx = unsafe { #cx.get_context() };
                         let polled = ::jenner::effective::Effective::poll_effect(__fut__.as_mut(), cx);
                         match polled {
                             ::jenner::effective::EffectResult::Done(_) => ::core::unreachable!(),
-                            ::jenner::effective::EffectResult::Item(x) => break x,
-                            ::jenner::effective::EffectResult::Failure(_) => ::core::unreachable!(),
+                            ::jenner::effective::EffectResult::Item(x) => {
+                                #step
+                                break #ready;
+                            }
+                            ::jenner::effective::EffectResult::Failure(__failure__) => #failure,
                             ::jenner::effective::EffectResult::Pending(_) => {
-                                #cx = yield ::jenner::__private::task::Poll::Pending;
+                                #pending
                             }
                         }
                     }
                 }}
             }
+            Expr::Await(await_) => {
+                let effects = match take_await_effects(await_) {
+                    Ok(effects) => effects,
+                    Err(error) => {
+                        let error = error.into_compile_error();
+                        return *i = parse_quote! { #error };
+                    }
+                };
+                if effects.cancellable {
+                    return *i = cancellable_await_error(await_);
+                }
+                if let Some((mac, short_circuit)) = join_macro(&await_.base) {
+                    let name = if short_circuit { "try_join" } else { "join" };
+                    let error = syn::Error::new_spanned(
+                        mac,
+                        format!("`{name}!` can only be awaited in async bodies"),
+                    )
+                    .into_compile_error();
+                    return *i = parse_quote! { #error };
+                }
+                if effects.maybe_async {
+                    blocking_variant(&mut await_.base);
+                }
+                let ExprAwait { base, .. } = await_;
+                self.visit_expr_mut(base);
+
+                let failure = self.lift_failure();
+                let await_ = if self.park {
+                    format_ident!("await_parked")
+                } else {
+                    format_ident!("await_blocking")
+                };
+                let effect = await_conversion(base);
+                *i = parse_quote! {
+                    match ::jenner::__private::#await_(::jenner::__private::pin::pin!(#effect)) {
+                        ::core::result::Result::Ok(x) => x,
+                        ::core::result::Result::Err(__failure__) => #failure,
+                    }
+                }
+            }
+            Expr::Macro(mac) if self.recoverable && is_macro(&mac.mac, "yield_err") => {
+                let err = match mac.mac.parse_body::<Expr>() {
+                    Ok(err) => err,
+                    Err(e) => {
+                        let error = e.into_compile_error();
+                        return *i = parse_quote! { #error };
+                    }
+                };
+                *i = parse_quote! { yield ::core::result::Result::Err(#err) };
+                self.yield_err = true;
+                self.visit_expr_mut(i);
+            }
+            Expr::Macro(mac) if self.uses.is_some() && is_macro(&mac.mac, "perform") => {
+                let mut op = match mac.mac.parse_body::<Expr>() {
+                    Ok(op) => op,
+                    Err(e) => {
+                        let error = e.into_compile_error();
+                        return *i = parse_quote! { #error };
+                    }
+                };
+                self.visit_expr_mut(&mut op);
+                *i = self.perform(op);
+            }
+            Expr::Yield(yield_) if self.uses.is_some() && !self.sync => {
+                self.visit_expr_yield_mut(yield_);
+                let cx = &self.cx;
+                *i = self.resume_scopes(parse_quote! {
+                    #cx = ::jenner::__private::PerformContext::into_context(#yield_)
+                });
+            }
+            Expr::Macro(mac) if is_macro(&mac.mac, "scope") => {
+                if self.sync {
+                    let error = syn::Error::new_spanned(
+                        &mac.mac,
+                        "`scope!` can only be used in async bodies",
+                    )
+                    .into_compile_error();
+                    return *i = parse_quote! { #error };
+                }
+                *i = match mac.mac.parse_body::<ExprClosure>() {
+                    Ok(closure) if closure.inputs.len() == 1 => self.scope_impl(closure),
+                    _ => {
+                        let error = syn::Error::new_spanned(
+                            &mac.mac.tokens,
+                            "expected a closure, eg `jenner::scope!(|s| ...)`",
+                        )
+                        .into_compile_error();
+                        parse_quote! { #error }
+                    }
+                };
+            }
+            Expr::Macro(mac) if is_macro(&mac.mac, "select") => {
+                if self.sync {
+                    let error = syn::Error::new_spanned(
+                        &mac.mac,
+                        "`select!` can only be used in async bodies",
+                    )
+                    .into_compile_error();
+                    return *i = parse_quote! { #error };
+                }
+                *i = match mac.mac.parse_body::<SelectInput>() {
+                    Ok(input) => self.select_impl(input),
+                    Err(e) => {
+                        let error = e.into_compile_error();
+                        parse_quote! { #error }
+                    }
+                };
+            }
+            Expr::Yield(yield_) if self.cancellable => {
+                self.visit_expr_yield_mut(yield_);
+                let exit = self.cancel_exit();
+                *i = self.resume_scopes(self.cancellation_point(yield_.clone().into(), exit));
+            }
+            Expr::Yield(yield_) if self.resumes.is_some() => {
+                self.visit_expr_yield_mut(yield_);
+                let throw = if self.fallible {
+                    quote! { return ::core::result::Result::Err(e) }
+                } else {
+                    quote! { ::jenner::__private::infallible(e) }
+                };
+                *i = if self.sync {
+                    parse_quote! {
+                        match ::jenner::__private::resumed(#yield_) {
+                            ::core::result::Result::Ok(x) => x,
+                            ::core::result::Result::Err(e) => #throw,
+                        }
+                    }
+                } else {
+                    let cx = &self.cx;
+                    self.resume_scopes(parse_quote! {{
+                        let (cx, resumed) = ::jenner::__private::ResumeContext::resumed(#yield_);
+                        #cx = cx;
+                        match resumed {
+                            ::core::result::Result::Ok(x) => x,
+                            ::core::result::Result::Err(e) => #throw,
+                        }
+                    }})
+                };
+            }
             Expr::Yield(yield_) if !self.sync => {
                 self.visit_expr_yield_mut(yield_);
-                *i = ExprAssign {
+                let assign: Expr = ExprAssign {
                     attrs: vec![],
                     left: Box::new(
                         ExprPath {
@@ -127,9 +742,16 @@ impl VisitMut for GenVisitor {
                     right: Box::new(yield_.clone().into()),
                 }
                 .into();
+                let assign = self.resume_scopes(assign);
+                *i = match self.budget_step() {
+                    Some(step) => parse_quote! {{ #assign; #step }},
+                    None => assign,
+                };
             }
-            Expr::Try(try_) => {
+            Expr::Try(try_) if !self.sync => {
                 let ExprTry { expr, .. } = try_;
+                // the operand can await or yield, eg `jenner::try_next(&mut x).await?`
+                self.visit_expr_mut(expr);
                 *i = parse_quote!(
                     match ::jenner::effective::SimpleTry::branch(#expr) {
                         ::core::ops::ControlFlow::Continue(x) => x,
@@ -147,50 +769,130 @@ impl VisitMut for GenVisitor {
                 }
             }
             Expr::ForLoop(for_loop) => {
-                visit_expr_for_loop_mut(self, for_loop);
-
-                let mut async_ = false;
-                // let mut fallible = false;
-
-                for attr in for_loop.attrs.drain(..) {
-                    if attr.path().is_ident("effect") {
-                        match attr.meta {
-                            syn::Meta::Path(_) => {}
-                            syn::Meta::List(list) => {
-                                fn parser(
-                                    input: syn::parse::ParseStream,
-                                ) -> syn::Result<Punctuated<Ident, Comma>>
-                                {
-                                    Punctuated::<Ident, Comma>::parse_terminated_with(
-                                        input,
-                                        Ident::parse_any,
-                                    )
-                                }
-                                let effects = parser.parse2(list.tokens).unwrap();
-
-                                for effect in effects {
-                                    match effect.to_string().as_str() {
-                                        "async" => async_ = true,
-                                        // "fallible" => fallible = true,
-                                        effect => panic!("unknown effect {effect}"),
-                                    }
-                                }
-                            }
-                            syn::Meta::NameValue(_) => {}
-                        }
+                let output = mem::take(&mut self.loop_output);
+                let ForEffects {
+                    mut async_,
+                    lending,
+                    maybe_async,
+                    concurrent,
+                    unordered,
+                } = match take_for_effects(&mut for_loop.attrs) {
+                    Ok(effects) => effects,
+                    Err(error) => {
+                        let error = error.into_compile_error();
+                        return *i = parse_quote! { #error };
+                    }
+                };
+
+                // the body is lowered into its own generator
+                if let Some(limit) = concurrent {
+                    let unsupported = if !async_ {
+                        Some("`concurrent` for-loops must also be `async`, eg `#[effect(async, concurrent(n))]`")
+                    } else if lending || maybe_async {
+                        Some("`concurrent` for-loops cannot also be `lending` or `maybe_async`")
+                    } else {
+                        None
+                    };
+                    if let Some(message) = unsupported {
+                        let error = syn::Error::new_spanned(&limit, message).into_compile_error();
+                        return *i = parse_quote! { #error };
                     }
+                    return *i = self.concurrent_for_impl(for_loop, limit, !unordered);
+                }
+                if self.cancellable {
+                    self.enter_loop(&for_loop.label);
+                    visit_expr_for_loop_mut(self, for_loop);
+                    self.exit_loop(&mut for_loop.label);
+                } else {
+                    visit_expr_for_loop_mut(self, for_loop);
                 }
 
-                if async_ {
-                    *i = self.async_for_impl(for_loop);
+                if maybe_async {
+                    async_ = true;
+                    if self.sync {
+                        blocking_variant(&mut for_loop.expr);
+                    }
+                }
+                if lending {
+                    *i = self.lending_for_impl(for_loop);
+                } else if async_ {
+                    *i = self.async_for_impl(for_loop, output);
                 }
             }
+            Expr::While(ExprWhile { label, .. }) | Expr::Loop(ExprLoop { label, .. })
+                if self.cancellable =>
+            {
+                self.enter_loop(label);
+                visit_expr_mut(self, i);
+                let (Expr::While(ExprWhile { label, .. }) | Expr::Loop(ExprLoop { label, .. })) = i
+                else {
+                    unreachable!()
+                };
+                self.exit_loop(label);
+            }
             i => visit_expr_mut(self, i),
         }
     }
 
+    fn visit_block_mut(&mut self, i: &mut Block) {
+        self.visit_stmts(&mut i.stmts);
+    }
+
+    fn visit_local_mut(&mut self, i: &mut Local) {
+        // `let output = #[effect(async)] for x in gen { ... };`
+        self.loop_output = matches!(&i.init, Some(init) if matches!(*init.expr, Expr::ForLoop(_)));
+        visit_local_mut(self, i);
+    }
+
+    fn visit_stmt_mut(&mut self, i: &mut Stmt) {
+        // `yield_err!(e);`, `perform!(op);`, `select! {}` and `scope!(...);` in statement position
+        if let Stmt::Macro(stmt) = i {
+            if (self.recoverable && is_macro(&stmt.mac, "yield_err"))
+                || (self.uses.is_some() && is_macro(&stmt.mac, "perform"))
+                || is_macro(&stmt.mac, "select")
+                || is_macro(&stmt.mac, "scope")
+            {
+                *i = Stmt::Expr(
+                    Expr::Macro(ExprMacro {
+                        attrs: stmt.attrs.clone(),
+                        mac: stmt.mac.clone(),
+                    }),
+                    stmt.semi_token,
+                );
+            }
+        }
+        visit_stmt_mut(self, i);
+    }
+
     fn visit_expr_yield_mut(&mut self, i: &mut ExprYield) {
+        let yield_err = mem::take(&mut self.yield_err);
         visit_expr_yield_mut(self, i);
+        if self.recoverable && !yield_err {
+            let expr = i.expr.get_or_insert_with(|| Box::new(parse_quote! { () }));
+            **expr = parse_quote! { ::core::result::Result::Ok(#expr) };
+        }
+        if let Some(outputs) = &self.outputs {
+            let expr = i.expr.get_or_insert_with(|| Box::new(parse_quote! { () }));
+            **expr = route_output(outputs, expr);
+        }
+        if self.lending {
+            if let Some(expr) = &mut i.expr {
+                **expr = parse_quote! { ::jenner::__private::IntoLent::into_lent(#expr) };
+            }
+        }
+        if self.uses.is_some() {
+            let expr = i.expr.get_or_insert_with(|| Box::new(parse_quote! { () }));
+            **expr = parse_quote! { ::jenner::__private::Suspend::Item(#expr) };
+            return;
+        }
+        if self.cancellable {
+            let expr = i.expr.get_or_insert_with(|| Box::new(parse_quote! { () }));
+            **expr = parse_quote! { ::jenner::__private::CancelSuspend::Ready(#expr) };
+            return;
+        }
+        if self.sync {
+            return;
+        }
         let ExprYield { expr, .. } = i;
         let expr = expr.get_or_insert_with(|| {
             Box::new(
@@ -217,7 +919,332 @@ impl VisitMut for GenVisitor {
 }
 
 impl GenVisitor {
-    fn async_for_impl(&self, for_loop: &mut ExprForLoop) -> Expr {
+    /// Pins an effect that is polled across yields.
+    /// Movable generators cannot hold borrows of their own locals, so the effect is boxed instead.
+    fn pin_local(&self) -> proc_macro2::TokenStream {
+        if self.movable {
+            quote! { ::std::boxed::Box::pin }
+        } else {
+            quote! { ::jenner::__private::pin::pin! }
+        }
+    }
+
+    /// Lifts `__failure__` into the failure of this body.
+    /// Fallible bodies return early, while infallible bodies only accept infallible effects.
+    fn lift_failure(&self) -> Expr {
+        if self.fallible {
+            parse_quote! {
+                return ::core::result::Result::Err(
+                    ::jenner::__private::FailureInto::failure_into(__failure__)
+                )
+            }
+        } else {
+            parse_quote! { ::jenner::__private::infallible(__failure__) }
+        }
+    }
+
+    /// Suspends the generator while waiting on a pending effect
+    fn suspend_pending(&self) -> Stmt {
+        let cx = &self.cx;
+        let suspend = if self.cancellable {
+            let point = self.cancellation_point(
+                parse_quote! { yield ::jenner::__private::CancelSuspend::Pending },
+                self.cancel_exit(),
+            );
+            parse_quote! { #point; }
+        } else if self.uses.is_some() {
+            parse_quote! {
+                #cx = ::jenner::__private::PerformContext::into_context(
+                    yield ::jenner::__private::Suspend::Pending(::jenner::effective::Async)
+                );
+            }
+        } else if self.resumes.is_some() {
+            parse_quote! {
+                #cx = ::jenner::__private::ResumeContext::into_context(
+                    yield ::jenner::__private::task::Poll::Pending
+                );
+            }
+        } else {
+            parse_quote! { #cx = yield ::jenner::__private::task::Poll::Pending; }
+        };
+        let suspend = self.poll_scopes(suspend);
+        if self.budget.is_none() {
+            return suspend;
+        }
+        // suspending gives the executor a turn, so the budget starts over
+        parse_quote! {{
+            __budget__.reset();
+            #suspend
+        }}
+    }
+
+    /// Counts a step that did not suspend against the budget,
+    /// suspending with a wake-up once it is used up so the executor can run other tasks
+    fn budget_step(&self) -> Option<Stmt> {
+        self.budget?;
+        let cx = &self.cx;
+        let suspend = self.suspend_pending();
+        Some(parse_quote! {
+            if __budget__.step() {
+                unsafe { #cx.get_context() }.waker().wake_by_ref();
+                #suspend
+            }
+        })
+    }
+
+    /// Polls the children of the enclosing scopes before suspending.
+    /// If any completed, the pending effect is polled again instead, as it may be waiting on them.
+    fn poll_scopes(&self, suspend: Stmt) -> Stmt {
+        if self.scopes.is_empty() {
+            return suspend;
+        }
+        let cx = &self.cx;
+        let scopes = &self.scopes;
+        parse_quote! {{
+            let mut __completed__ = false;
+            {
+                let cx = unsafe { #cx.get_context() };
+                #(__completed__ |= #scopes.poll_children(cx);)*
+            }
+            if !__completed__ {
+                #suspend
+            }
+        }}
+    }
+
+    /// Polls the children of the enclosing scopes once the generator is resumed from a `yield`,
+    /// so they keep making progress while the body is producing items
+    fn resume_scopes(&self, resumed: Expr) -> Expr {
+        if self.scopes.is_empty() {
+            return resumed;
+        }
+        let cx = &self.cx;
+        let scopes = &self.scopes;
+        parse_quote! {{
+            let __resumed__ = #resumed;
+            {
+                let cx = unsafe { #cx.get_context() };
+                #(#scopes.poll_children(cx);)*
+            }
+            __resumed__
+        }}
+    }
+
+    /// Resumes from `suspend`, evaluating `on_cancel` if the generator was cancelled while suspended
+    fn cancellation_point(&self, suspend: Expr, on_cancel: Expr) -> Expr {
+        let cx = &self.cx;
+        parse_quote! {{
+            let __resume__ = #suspend;
+            let __cancelled__ = __resume__.cancelled();
+            #cx = __resume__.into_context();
+            if __cancelled__ {
+                #on_cancel
+            }
+        }}
+    }
+
+    /// Runs the `on_cancel!` blocks in scope, most recent first, then stops the generator
+    fn cancel_exit(&self) -> Expr {
+        match &self.cancel_scope {
+            // parenthesised, or the label and the leading `::` parse as a labelled expression
+            Some(label) => parse_quote! { break #label (::core::ops::ControlFlow::Break(())) },
+            None => parse_quote! {
+                loop {
+                    yield ::jenner::__private::CancelSuspend::Cancelled;
+                }
+            },
+        }
+    }
+
+    /// Tracks the label of a loop in a cancellable body,
+    /// making one up in case an `on_cancel!` scope inside needs to exit the loop by its label
+    fn enter_loop(&mut self, label: &Option<Label>) {
+        let label = match label {
+            Some(label) => label.name.clone(),
+            None => {
+                self.loop_labels += 1;
+                Lifetime::new(&format!("'__loop_{}", self.loop_labels), Span::call_site())
+            }
+        };
+        self.loops.push((label, false));
+    }
+
+    /// Labels the loop with the label made up by [`Self::enter_loop`], if it was used
+    fn exit_loop(&mut self, label: &mut Option<Label>) {
+        let (name, used) = self.loops.pop().unwrap();
+        if used && label.is_none() {
+            *label = Some(Label {
+                name,
+                colon_token: Default::default(),
+            });
+        }
+    }
+
+    /// Visits the statements of a block, or of the generator body
+    fn visit_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        if self.cancellable {
+            let on_cancel = stmts.iter().position(
+                |stmt| matches!(stmt, Stmt::Macro(stmt) if is_macro(&stmt.mac, "on_cancel")),
+            );
+            if let Some(index) = on_cancel {
+                return self.on_cancel_scope(stmts, index);
+            }
+        }
+        stmts.iter_mut().for_each(|stmt| self.visit_stmt_mut(stmt));
+    }
+
+    /// Lowers the `on_cancel!` at `stmts[index]` once, as the exit of a labelled block around
+    /// the rest of its scope. A cancelled await breaks out of the block,
+    /// dropping the locals declared since, runs the cleanup, then exits the enclosing scope.
+    fn on_cancel_scope(&mut self, stmts: &mut Vec<Stmt>, index: usize) {
+        let mut rest = stmts.split_off(index + 1);
+        let Some(Stmt::Macro(on_cancel)) = stmts.pop() else {
+            unreachable!()
+        };
+        for stmt in stmts.iter_mut() {
+            self.visit_stmt_mut(stmt);
+        }
+
+        let mut cleanup = match on_cancel.mac.parse_body_with(Block::parse_within) {
+            Ok(stmts) => Block {
+                brace_token: Default::default(),
+                stmts,
+            },
+            Err(e) => {
+                let error = e.into_compile_error();
+                stmts.push(parse_quote! { #error; });
+                stmts.append(&mut rest);
+                return;
+            }
+        };
+        // cancellation is only delivered once, so the cleanup is never cancelled
+        let outer = self.cancel_scope.take();
+        self.visit_block_mut(&mut cleanup);
+        // then the cleanup of the enclosing scope runs
+        self.cancel_scope = outer.clone();
+        let exit = self.cancel_exit();
+
+        // items are visible in the whole block, not just after the `on_cancel!`
+        let (items, rest): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|stmt| matches!(stmt, Stmt::Item(_)));
+        stmts.extend(items);
+        let mut rest = Block {
+            brace_token: Default::default(),
+            stmts: rest,
+        };
+        let label = Lifetime::new(
+            &format!("'__on_cancel_{}", self.cancel_scopes),
+            Span::call_site(),
+        );
+        self.cancel_scopes += 1;
+        // an unlabelled `break` or `continue` cannot exit the labelled block
+        if let Some((loop_label, used)) = self.loops.last_mut() {
+            let mut exits = CancelScopeExits {
+                label: loop_label,
+                inner: 0,
+                used: false,
+            };
+            exits.visit_block_mut(&mut rest);
+            *used |= exits.used;
+        }
+        self.cancel_scope = Some(label.clone());
+        self.visit_block_mut(&mut rest);
+        self.cancel_scope = outer;
+
+        stmts.push(parse_quote! {
+            // unused if nothing after the `on_cancel!` suspends
+            #[allow(unused_labels)]
+            let __cancel__ = #label: { ::core::ops::ControlFlow::Continue(#rest) };
+        });
+        stmts.push(Stmt::Expr(
+            parse_quote! {
+                match __cancel__ {
+                    ::core::ops::ControlFlow::Continue(x) => x,
+                    ::core::ops::ControlFlow::Break(()) => {
+                        #cleanup
+                        #exit
+                    }
+                }
+            },
+            None,
+        ));
+    }
+
+    /// Suspends the generator with the request for `op`, until a handler has answered it
+    fn perform(&self, op: Expr) -> Expr {
+        let ops = &self.uses;
+        let request = quote! {
+            let (__request__, __response__) = ::jenner::__private::request::<#ops, _, _>(#op);
+            let __resumed__ = yield ::jenner::__private::Suspend::Perform(__request__);
+        };
+        if self.sync {
+            return parse_quote! {{
+                #request
+                __response__.resumed(__resumed__).1
+            }};
+        }
+        let cx = &self.cx;
+        parse_quote! {{
+            #request
+            let (cx, output) = __response__.resumed(__resumed__);
+            #cx = cx.unwrap();
+            output
+        }}
+    }
+
+    /// Polls `__gen__` for its next item, suspending the generator while it is pending
+    fn poll_next(&self, poll: Path) -> Expr {
+        let failure = self.lift_failure();
+        // lending effects are only lent by their own poll, so they are not parked on
+        if self.park && poll.segments.last().unwrap().ident == "poll_effect" {
+            return parse_quote! {
+                match ::jenner::__private::park_on(__gen__.as_mut()) {
+                    ::jenner::effective::EffectResult::Done(_) => None,
+                    ::jenner::effective::EffectResult::Item(x) => Some(x),
+                    ::jenner::effective::EffectResult::Failure(__failure__) => #failure,
+                    ::jenner::effective::EffectResult::Pending(_) => unreachable!(),
+                }
+            };
+        }
+        if self.sync {
+            return parse_quote! {{
+                let __waker__ = ::jenner::__private::noop_waker();
+                let mut __cx__ = ::jenner::__private::task::Context::from_waker(&__waker__);
+                match #poll(__gen__.as_mut(), &mut __cx__) {
+                    ::jenner::effective::EffectResult::Done(_) => None,
+                    ::jenner::effective::EffectResult::Item(x) => Some(x),
+                    ::jenner::effective::EffectResult::Failure(__failure__) => #failure,
+                    ::jenner::effective::EffectResult::Pending(p) => ::jenner::__private::blocking(p),
+                }
+            }};
+        }
+
+        let cx = &self.cx;
+        let step = self.budget_step();
+        let pending = self.suspend_pending();
+        parse_quote! {
+            loop {
+                let cx = unsafe { #cx.get_context() };
+                let polled = #poll(__gen__.as_mut(), cx);
+                match polled {
+                    ::jenner::effective::EffectResult::Done(_) => break None,
+                    ::jenner::effective::EffectResult::Item(x) => {
+                        #step
+                        break Some(x);
+                    }
+                    ::jenner::effective::EffectResult::Failure(__failure__) => #failure,
+                    ::jenner::effective::EffectResult::Pending(_) => {
+                        #pending
+                    }
+                };
+            }
+        }
+    }
+
+    /// Lowers an `#[effect(async)]` for-loop, which evaluates to the final value of the generator
+    /// if `output` is set, and `()` otherwise
+    fn async_for_impl(&self, for_loop: &mut ExprForLoop, output: bool) -> Expr {
         let ExprForLoop {
             attrs,
             label,
@@ -227,24 +1254,405 @@ impl GenVisitor {
             ..
         } = for_loop;
 
-        let cx = &self.cx;
+        let pin = self.pin_local();
+        let next = self.poll_next(new_path!(::jenner::effective::Effective::poll_effect));
+        let output = output.then(|| {
+            quote! {
+                {
+                    // weak form of specialisation.
+                    #[allow(unused_imports)]
+                    use ::jenner::__private::{TakeOutputReturn, TakeOutputUnit};
+                    (&mut ::jenner::__private::TakeOutput(__gen__.as_mut())).take_output()
+                }
+            }
+        });
+        let effect = for_conversion(expr);
         parse_quote! {
             #(#attrs)*
             {
-                let mut __gen__ = ::jenner::__private::pin::pin!(#expr);
+                let mut __gen__ = #pin(#effect);
                 #label loop {
-                    let __next__ = loop {
-                        let cx = unsafe { #cx.get_context() };
-                        let polled = ::jenner::effective::Effective::poll_effect(__gen__.as_mut(), cx);
-                        match polled {
-                            ::jenner::effective::EffectResult::Done(_) => break None,
-                            ::jenner::effective::EffectResult::Item(x) => break Some(x),
-                            ::jenner::effective::EffectResult::Failure(_) => ::core::unreachable!(),
-                            ::jenner::effective::EffectResult::Pending(_) => {
-                                #cx = yield ::jenner::__private::task::Poll::Pending;
+                    let __next__ = #next;
+
+                    if let Some(#pat) = __next__ { #body } else { break };
+                };
+                #output
+            }
+        }
+    }
+
+    /// Runs the bodies of a `#[effect(async, concurrent(n))]` for-loop as generators of their own,
+    /// passing on whatever they yield.
+    /// `break` and `continue` in a body return from its generator, and `break` stops the loop.
+    fn concurrent_for_impl(
+        &mut self,
+        for_loop: &mut ExprForLoop,
+        mut limit: Expr,
+        ordered: bool,
+    ) -> Expr {
+        let unsupported = if self.sync {
+            Some("`concurrent` for-loops can only be used in async bodies")
+        } else if self.uses.is_some() || self.cancellable || self.resumes.is_some() || self.lending
+        {
+            Some("`concurrent` for-loops are not supported with `uses`, `cancellable`, `resumes` or `lending`")
+        } else {
+            None
+        };
+        if let Some(message) = unsupported {
+            let error = syn::Error::new_spanned(for_loop.for_token, message).into_compile_error();
+            return parse_quote! { #error };
+        }
+
+        let ExprForLoop {
+            label,
+            pat,
+            expr,
+            body,
+            ..
+        } = for_loop;
+        self.visit_expr_mut(expr);
+        self.visit_expr_mut(&mut limit);
+
+        let mut exits = LoopExitVisitor {
+            label: &*label,
+            fallible: self.fallible,
+            inner: vec![],
+            error: None,
+        };
+        exits.visit_block_mut(body);
+        if let Some(error) = exits.error {
+            let error = error.into_compile_error();
+            return parse_quote! { #error };
+        }
+
+        let mut visitor = GenVisitor::new(false, true, self.fallible);
+        visitor.movable = self.movable;
+        visitor.outputs = self.outputs.clone();
+        visitor.recoverable = self.recoverable;
+        visitor.visit_block_mut(body);
+        let body_cx = &visitor.cx;
+        let (body_impl, continue_): (Path, Expr) = if self.fallible {
+            (
+                new_path!(::jenner::__private::AsyncFallibleGeneratorImpl),
+                parse_quote! { ::core::result::Result::Ok(::core::ops::ControlFlow::Continue(())) },
+            )
+        } else {
+            (
+                new_path!(::jenner::__private::AsyncGeneratorImpl),
+                parse_quote! { ::core::ops::ControlFlow::Continue(()) },
+            )
+        };
+
+        let cx = &self.cx;
+        let yield_: Expr = if self.yields {
+            parse_quote! { #cx = yield ::jenner::__private::task::Poll::Ready(__yielded__) }
+        } else {
+            parse_quote! { match ::core::convert::identity::<::core::convert::Infallible>(__yielded__) {} }
+        };
+        let pin = self.pin_local();
+        let next = self.poll_next(new_path!(::jenner::effective::Effective::poll_effect));
+        let effect = for_conversion(expr);
+        parse_quote! {{
+            let mut __gen__ = #pin(::jenner::__private::ConcurrentFor::new(
+                #effect,
+                #limit,
+                #ordered,
+                |__item__| {
+                    let __item__ = ::jenner::__private::Owned(__item__);
+                    #body_impl::new(static |mut #body_cx: ::jenner::__private::UnsafeContextRef| {
+                        let #pat = __item__.into_inner();
+                        #body;
+                        #continue_
+                    })
+                },
+            ));
+            loop {
+                let __next__ = #next;
+                match __next__ {
+                    ::core::option::Option::Some(__yielded__) => #yield_,
+                    ::core::option::Option::None => break,
+                }
+            }
+        }}
+    }
+
+    /// Lowers the body of a `jenner::scope!(|s| body)` into the generator,
+    /// then polls the children spawned into the scope until they have all completed
+    /// Exits from the body break out of it first, and are taken once the children are joined.
+    fn scope_impl(&mut self, closure: ExprClosure) -> Expr {
+        let depth = self.scopes.len();
+        let scope = format_ident!("__scope{}__", depth);
+        let pat = &closure.inputs[0];
+        let mut body = *closure.body;
+        self.scopes.push(scope.clone());
+        self.visit_expr_mut(&mut body);
+        self.scopes.pop();
+
+        let mut exits = ScopeExits {
+            label: Lifetime::new(&format!("'__scope{depth}"), Span::call_site()),
+            exit_ty: format_ident!("__ScopeExit{}", depth),
+            inner: vec![],
+            exits: vec![],
+        };
+        exits.visit_expr_mut(&mut body);
+
+        let cx = &self.cx;
+        let pending = self.suspend_pending();
+        let join = quote! {
+            loop {
+                let cx = unsafe { #cx.get_context() };
+                let completed = #scope.poll_children(cx);
+                if #scope.is_empty() {
+                    break;
+                }
+                if !completed {
+                    #pending
+                }
+            }
+        };
+        if exits.exits.is_empty() {
+            return parse_quote! {{
+                let #scope = <::jenner::Scope as ::core::default::Default>::default();
+                let __scoped__ = {
+                    let #pat = &#scope;
+                    #body
+                };
+                #join
+                __scoped__
+            }};
+        }
+
+        let ScopeExits {
+            label,
+            exit_ty,
+            exits,
+            ..
+        } = exits;
+        let variants: Vec<_> = (0..exits.len())
+            .map(|i| format_ident!("Exit{}", i))
+            .collect();
+        let tys: Vec<_> = (0..exits.len()).map(|i| format_ident!("T{}", i)).collect();
+        parse_quote! {{
+            enum #exit_ty<#(#tys),*> {
+                #(#variants(#tys),)*
+            }
+            let #scope = <::jenner::Scope as ::core::default::Default>::default();
+            let __scoped__ = #label: {
+                let #pat = &#scope;
+                ::core::ops::ControlFlow::Continue(#body)
+            };
+            #join
+            match __scoped__ {
+                ::core::ops::ControlFlow::Continue(x) => x,
+                #(
+                    #[allow(unused_variables)]
+                    ::core::ops::ControlFlow::Break(#exit_ty::#variants(__exit__)) => #exits,
+                )*
+            }
+        }}
+    }
+
+    /// Polls every effect of a `join!` until they have all completed,
+    /// suspending the generator while any are pending.
+    /// `try_join!` lifts the first failure as soon as it happens, dropping the other effects,
+    /// while `join!` waits for them all before lifting the first failure.
+    fn join_impl(&mut self, mac: &Macro, short_circuit: bool, ready: Expr, pending: Stmt) -> Expr {
+        let mut effects = match mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        {
+            Ok(effects) if effects.is_empty() => {
+                let name = if short_circuit { "try_join" } else { "join" };
+                let error =
+                    syn::Error::new_spanned(mac, format!("`{name}!` requires at least one effect"))
+                        .into_compile_error();
+                return parse_quote! { #error };
+            }
+            Ok(effects) => effects,
+            Err(e) => {
+                let error = e.into_compile_error();
+                return parse_quote! { #error };
+            }
+        };
+        for effect in &mut effects {
+            self.visit_expr_mut(effect);
+        }
+
+        let n = effects.len();
+        let joins: Vec<_> = (0..n).map(|i| format_ident!("__join{}__", i)).collect();
+        let outs: Vec<_> = (0..n).map(|i| format_ident!("__out{}__", i)).collect();
+        let effects = effects.iter().map(await_conversion);
+
+        let cx = &self.cx;
+        let pin = self.pin_local();
+        let failure = self.lift_failure();
+        let polls = outs.iter().zip(&joins).map(|(out, join)| {
+            let (item, on_failure): (Expr, Expr) = if short_circuit {
+                (parse_quote! { ::core::option::Option::Some(x) }, failure.clone())
+            } else {
+                (
+                    parse_quote! { ::core::option::Option::Some(::core::result::Result::Ok(x)) },
+                    parse_quote! {
+                        #out = ::core::option::Option::Some(::core::result::Result::Err(__failure__))
+                    },
+                )
+            };
+            quote! {
+                if #out.is_none() {
+                    match ::jenner::effective::Effective::poll_effect(#join.as_mut(), cx) {
+                        ::jenner::effective::EffectResult::Done(_) => ::core::unreachable!(),
+                        ::jenner::effective::EffectResult::Item(x) => #out = #item,
+                        ::jenner::effective::EffectResult::Failure(__failure__) => #on_failure,
+                        ::jenner::effective::EffectResult::Pending(_) => {}
+                    }
+                }
+            }
+        });
+        let outputs = outs.iter().map(|out| -> Expr {
+            if short_circuit {
+                parse_quote! { #out.take().unwrap() }
+            } else {
+                parse_quote! {
+                    match #out.take().unwrap() {
+                        ::core::result::Result::Ok(x) => x,
+                        ::core::result::Result::Err(__failure__) => #failure,
+                    }
+                }
+            }
+        });
+        parse_quote! {{
+            #(
+                let mut #joins = #pin(#effects);
+                let mut #outs = ::core::option::Option::None;
+            )*
+            loop {
+                let cx = unsafe { #cx.get_context() };
+                #(#polls)*
+                if #(#outs.is_some())&&* {
+                    let x = (#(#outputs,)*);
+                    break #ready;
+                }
+                #pending
+            }
+        }}
+    }
+
+    /// Polls each branch in turn until one completes with an output matching its pattern,
+    /// suspending the generator while they are all pending
+    fn select_impl(&mut self, input: SelectInput) -> Expr {
+        let SelectInput {
+            biased,
+            mut arms,
+            else_,
+        } = input;
+        for arm in &mut arms {
+            if let Some(guard) = &mut arm.guard {
+                self.visit_expr_mut(guard);
+            }
+            self.visit_expr_mut(&mut arm.effect);
+            self.visit_expr_mut(&mut arm.body);
+        }
+        let mut else_ = else_.unwrap_or_else(|| {
+            parse_quote! {
+                ::core::panic!("all `select!` branches are disabled, and there is no `else` branch")
+            }
+        });
+        self.visit_expr_mut(&mut else_);
+
+        let n = arms.len();
+        let indices: Vec<_> = (0..n).collect();
+        let branches: Vec<_> = (0..n).map(|i| format_ident!("__branch{}__", i)).collect();
+        let variants: Vec<_> = (0..n).map(|i| format_ident!("Branch{}", i)).collect();
+        let tys: Vec<_> = (0..n).map(|i| format_ident!("T{}", i)).collect();
+        let effects = arms.iter().map(|arm| &arm.effect);
+        let guards = arms.iter().map(|arm| match &arm.guard {
+            Some(guard) => quote! { #guard },
+            None => quote! { true },
+        });
+        let pats = arms.iter().map(|arm| &arm.pat);
+        let ref_pats = arms.iter().map(|arm| ref_pattern(&arm.pat));
+        let bodies = arms.iter().map(|arm| &arm.body);
+
+        let cx = &self.cx;
+        let pin = self.pin_local();
+        let failure = self.lift_failure();
+        let pending = self.suspend_pending();
+        let start = if biased {
+            quote! { 0 }
+        } else {
+            quote! {{
+                static __SELECT_START__: ::core::sync::atomic::AtomicUsize =
+                    ::core::sync::atomic::AtomicUsize::new(0);
+                ::jenner::__private::select_start(&__SELECT_START__, #n)
+            }}
+        };
+        parse_quote! {{
+            enum __Selected<#(#tys),*> {
+                #(#variants(#tys),)*
+                Else,
+            }
+            // the guards are evaluated first. Disabled branches are created but never polled
+            let mut __enabled__: [bool; #n] = [#(#guards),*];
+            #(
+                let mut #branches = #pin({
+                    // weak form of specialisation.
+                    #[allow(unused_imports)]
+                    use ::jenner::__private::{SelectEffective, SelectFuture};
+                    (&mut ::jenner::__private::SelectBranch::new(#effects)).into_branch()
+                });
+            )*
+            let __start__ = #start;
+            let __selected__ = '__select__: loop {
+                let cx = unsafe { #cx.get_context() };
+                for __offset__ in 0..#n {
+                    match (__start__ + __offset__) % #n {
+                        #(#indices if __enabled__[#indices] => {
+                            match ::jenner::__private::poll_branch(#branches.as_mut(), cx) {
+                                ::jenner::__private::task::Poll::Ready(::core::result::Result::Ok(__out__)) => {
+                                    // outputs that don't match the pattern disable the branch
+                                    #[allow(unused_variables, unreachable_patterns)]
+                                    match &__out__ {
+                                        #ref_pats => break '__select__ __Selected::#variants(__out__),
+                                        _ => __enabled__[#indices] = false,
+                                    };
+                                }
+                                ::jenner::__private::task::Poll::Ready(::core::result::Result::Err(__failure__)) => #failure,
+                                ::jenner::__private::task::Poll::Pending => {}
                             }
-                        };
-                    };
+                        })*
+                        _ => {}
+                    }
+                }
+                if !__enabled__.contains(&true) {
+                    break __Selected::Else;
+                }
+                #pending
+            };
+            match __selected__ {
+                #(__Selected::#variants(#pats) => #bodies,)*
+                __Selected::Else => #else_,
+                #[allow(unreachable_patterns)]
+                _ => ::core::unreachable!(),
+            }
+        }}
+    }
+
+    fn lending_for_impl(&self, for_loop: &mut ExprForLoop) -> Expr {
+        let ExprForLoop {
+            attrs,
+            label,
+            pat,
+            expr,
+            body,
+            ..
+        } = for_loop;
+
+        let pin = self.pin_local();
+        let next = self.poll_next(new_path!(::jenner::LendingEffective::poll_lend));
+        parse_quote! {
+            #(#attrs)*
+            {
+                let mut __gen__ = #pin(#expr);
+                #label loop {
+                    let __next__ = #next;
 
                     if let Some(#pat) = __next__ { #body } else { break };
                 };
@@ -1,11 +1,11 @@
 #![feature(drain_filter)]
 
-use parse::AttrGenerator;
+use entry::Entry;
+use parse::{AttrGenerator, EffectArg, EffectValue, OutputArg};
 use proc_macro::TokenStream as TokenStream1;
-use proc_macro2::Ident;
-use quote::ToTokens;
 use syn::{
-    ext::IdentExt, parse::Parser, parse_macro_input, punctuated::Punctuated, token::Comma, Error,
+    parse::Parser, parse_macro_input, parse_quote, punctuated::Punctuated, Error, Ident, Token,
+    Type,
 };
 
 macro_rules! new_path {
@@ -41,6 +41,8 @@ macro_rules! segments {
 }
 
 mod break_visit;
+mod dispatch;
+mod entry;
 mod gen_visit;
 mod parse;
 mod process;
@@ -49,19 +51,72 @@ mod process;
 pub fn effect(args: TokenStream1, input: TokenStream1) -> TokenStream1 {
     let mut input = parse_macro_input!(input as AttrGenerator);
 
-    fn parser(input: syn::parse::ParseStream) -> syn::Result<Punctuated<Ident, Comma>> {
-        Punctuated::<Ident, Comma>::parse_terminated_with(input, Ident::parse_any)
-    }
-    let effects = match parser.parse(args) {
+    let effects = match EffectArg::parse_all.parse(args) {
         Ok(x) => x,
         Err(e) => return e.to_compile_error().into(),
     };
-    for effect in effects {
-        match effect.to_string().as_str() {
-            "fallible" => input.fallible = true,
-            "yields" => input.yields = true,
+    for EffectArg { name, value } in effects {
+        match (name.to_string().as_str(), value) {
+            ("fallible", EffectValue::Flag) => input.fallible = true,
+            ("fallible", EffectValue::List(tokens)) => match syn::parse2::<Ident>(tokens) {
+                Ok(mode) if mode == "recoverable" => {
+                    input.fallible = true;
+                    input.recoverable = true;
+                }
+                Ok(mode) => {
+                    return Error::new(mode.span(), "unknown fallible mode")
+                        .into_compile_error()
+                        .into()
+                }
+                Err(e) => return e.to_compile_error().into(),
+            },
+            ("yields", EffectValue::Flag) => input.yields = true,
+            ("lending", EffectValue::Flag) => input.lending = true,
+            ("clone", EffectValue::Flag) => input.clone = true,
+            ("movable", EffectValue::Flag) => input.movable = true,
+            ("maybe_async", EffectValue::Flag) => input.maybe_async = true,
+            ("dispatch", EffectValue::Flag) => input.dispatch = true,
+            ("cancellable", EffectValue::Flag) => input.cancellable = true,
+            ("budget", EffectValue::Int(steps)) => match steps.base10_parse::<u32>() {
+                Ok(0) => {
+                    return Error::new(steps.span(), "the budget must allow at least one step")
+                        .into_compile_error()
+                        .into()
+                }
+                Ok(steps) => input.budget = Some(steps),
+                Err(e) => return e.to_compile_error().into(),
+            },
+            ("yields", EffectValue::Type(ty)) => {
+                input.yields = true;
+                input.yield_ty = Some(*ty);
+            }
+            ("yields", EffectValue::List(tokens)) => match OutputArg::parse_all.parse2(tokens) {
+                Ok(outputs) => {
+                    input.yields = true;
+                    input.outputs = outputs.into_iter().collect();
+                }
+                Err(e) => return e.to_compile_error().into(),
+            },
+            ("resumes", EffectValue::List(tokens)) => match syn::parse2(tokens) {
+                Ok(ty) => input.resumes = Some(ty),
+                Err(e) => return e.to_compile_error().into(),
+            },
+            ("uses", EffectValue::List(tokens)) => {
+                match Punctuated::<Type, Token![,]>::parse_terminated.parse2(tokens) {
+                    Ok(ops) => {
+                        let list =
+                            ops.into_iter()
+                                .rev()
+                                .fold(parse_quote! { () }, |rest: Type, op| {
+                                    parse_quote! { (#op, #rest) }
+                                });
+                        input.uses = Some(list);
+                    }
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            }
             _other => {
-                return Error::new(effect.span(), "unknown effect")
+                return Error::new(name.span(), "unknown effect")
                     .into_compile_error()
                     .into()
             }
@@ -69,7 +124,57 @@ pub fn effect(args: TokenStream1, input: TokenStream1) -> TokenStream1 {
     }
 
     input
-        .process()
-        .map_or_else(|e| e.to_compile_error(), ToTokens::into_token_stream)
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[proc_macro_attribute]
+pub fn test(args: TokenStream1, input: TokenStream1) -> TokenStream1 {
+    entry(args, parse_macro_input!(input as AttrGenerator), true)
+}
+
+#[proc_macro_attribute]
+pub fn main(args: TokenStream1, input: TokenStream1) -> TokenStream1 {
+    entry(args, parse_macro_input!(input as AttrGenerator), false)
+}
+
+fn entry(args: TokenStream1, generator: AttrGenerator, test: bool) -> TokenStream1 {
+    let mut entry = Entry {
+        generator,
+        test,
+        timeout: None,
+    };
+
+    let options = match EffectArg::parse_all.parse(args) {
+        Ok(x) => x,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    for EffectArg { name, value } in options {
+        match (name.to_string().as_str(), value) {
+            ("fallible", EffectValue::Flag) => entry.generator.fallible = true,
+            ("timeout", EffectValue::Int(ms)) => match ms.base10_parse::<u64>() {
+                Ok(0) => {
+                    return Error::new(ms.span(), "the timeout must be at least 1ms")
+                        .into_compile_error()
+                        .into()
+                }
+                Ok(ms) => entry.timeout = Some(ms),
+                Err(e) => return e.to_compile_error().into(),
+            },
+            _other => {
+                return Error::new(
+                    name.span(),
+                    "unknown option, expected `fallible` or `timeout = ms`",
+                )
+                .into_compile_error()
+                .into()
+            }
+        }
+    }
+
+    entry
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
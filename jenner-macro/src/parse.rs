@@ -1,9 +1,30 @@
-use syn::{parse::Parse, ItemFn, Result};
+use proc_macro2::{Ident, TokenStream};
+use syn::{
+    ext::IdentExt, parse::Parse, punctuated::Punctuated, token, Expr, ItemFn, LitInt, Pat, Result,
+    Token, Type,
+};
 
+#[derive(Clone)]
 pub struct AttrGenerator {
     pub func: ItemFn,
     pub yields: bool,
+    pub yield_ty: Option<Type>,
     pub fallible: bool,
+    pub resumes: Option<Type>,
+    pub lending: bool,
+    pub clone: bool,
+    pub movable: bool,
+    pub maybe_async: bool,
+    pub dispatch: bool,
+    /// The named outputs of `yields(name: Type, ...)`
+    pub outputs: Vec<OutputArg>,
+    /// `fallible(recoverable)`, errors can be yielded without completing the generator
+    pub recoverable: bool,
+    /// The operations of `uses(...)`, as a list `(A, (B, ()))`
+    pub uses: Option<Type>,
+    pub cancellable: bool,
+    /// `budget = n`, how many steps the generator takes without suspending before it yields to the executor
+    pub budget: Option<u32>,
 }
 
 impl Parse for AttrGenerator {
@@ -11,7 +32,159 @@ impl Parse for AttrGenerator {
         Ok(AttrGenerator {
             func: input.parse()?,
             yields: false,
+            yield_ty: None,
             fallible: false,
+            resumes: None,
+            lending: false,
+            clone: false,
+            movable: false,
+            maybe_async: false,
+            dispatch: false,
+            outputs: vec![],
+            recoverable: false,
+            uses: None,
+            cancellable: false,
+            budget: None,
+        })
+    }
+}
+
+/// A single argument to an `#[effect(...)]` attribute
+pub struct EffectArg {
+    pub name: Ident,
+    pub value: EffectValue,
+}
+
+pub enum EffectValue {
+    /// `name`
+    Flag,
+    /// `name = Type`
+    Type(Box<Type>),
+    /// `name = 64`
+    Int(LitInt),
+    /// `name(...)`
+    List(TokenStream),
+}
+
+impl Parse for EffectArg {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let name = Ident::parse_any(input)?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            if input.peek(LitInt) {
+                EffectValue::Int(input.parse()?)
+            } else {
+                EffectValue::Type(input.parse()?)
+            }
+        } else if input.peek(token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            EffectValue::List(content.parse()?)
+        } else {
+            EffectValue::Flag
+        };
+        Ok(EffectArg { name, value })
+    }
+}
+
+impl EffectArg {
+    pub fn parse_all(input: syn::parse::ParseStream) -> Result<Punctuated<Self, token::Comma>> {
+        Punctuated::parse_terminated(input)
+    }
+}
+
+/// A single output of a multi-output generator, `name: Type`
+#[derive(Clone)]
+pub struct OutputArg {
+    pub name: Ident,
+    pub ty: Type,
+}
+
+impl Parse for OutputArg {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        Ok(OutputArg { name, ty })
+    }
+}
+
+impl OutputArg {
+    pub fn parse_all(input: syn::parse::ParseStream) -> Result<Punctuated<Self, token::Comma>> {
+        Punctuated::parse_terminated(input)
+    }
+}
+
+/// The input of `select! { biased; pattern = effect => handler, else => handler }`
+pub struct SelectInput {
+    pub biased: bool,
+    pub arms: Vec<SelectArm>,
+    pub else_: Option<Expr>,
+}
+
+pub struct SelectArm {
+    pub pat: Pat,
+    pub effect: Expr,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+impl Parse for SelectInput {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self> {
+        let biased = input.peek(Ident::peek_any) && input.peek2(Token![;]) && {
+            let ident = Ident::parse_any(input)?;
+            if ident != "biased" {
+                return Err(syn::Error::new(ident.span(), "expected `biased`"));
+            }
+            input.parse::<Token![;]>()?;
+            true
+        };
+
+        let mut arms = vec![];
+        let mut else_ = None;
+        while !input.is_empty() {
+            let body = if input.peek(Token![else]) {
+                let token = input.parse::<Token![else]>()?;
+                input.parse::<Token![=>]>()?;
+                let body: Expr = input.parse()?;
+                if else_.replace(body.clone()).is_some() {
+                    return Err(syn::Error::new(token.span, "duplicate `else` branch"));
+                }
+                body
+            } else {
+                let pat = Pat::parse_multi_with_leading_vert(input)?;
+                input.parse::<Token![=]>()?;
+                let effect = input.parse()?;
+                let guard = if input.peek(Token![,]) && input.peek2(Token![if]) {
+                    input.parse::<Token![,]>()?;
+                    input.parse::<Token![if]>()?;
+                    Some(input.parse()?)
+                } else {
+                    None
+                };
+                input.parse::<Token![=>]>()?;
+                let body: Expr = input.parse()?;
+                arms.push(SelectArm {
+                    pat,
+                    effect,
+                    guard,
+                    body: body.clone(),
+                });
+                body
+            };
+            // like match arms, blocks don't need a trailing comma
+            let block = matches!(body, Expr::Block(_) | Expr::If(_) | Expr::Match(_));
+            if input.parse::<Option<Token![,]>>()?.is_none() && !block && !input.is_empty() {
+                return Err(input.error("expected `,` after a `select!` branch"));
+            }
+        }
+        if arms.is_empty() {
+            return Err(input.error("`select!` requires at least one branch"));
+        }
+        Ok(SelectInput {
+            biased,
+            arms,
+            else_,
         })
     }
 }
@@ -1,18 +1,50 @@
 use std::mem;
 
-use quote::format_ident;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
 use syn::punctuated::Punctuated;
 use syn::{
-    AssocType, GenericArgument, ItemFn, Path, PathArguments, Result, Signature, Stmt, TraitBound,
-    Type, TypeImplTrait, TypeParamBound, TypePath, TypeTuple,
+    parse_quote,
+    visit_mut::{self, VisitMut},
+    AssocType, Error, FnArg, GenericArgument, GenericParam, ItemFn, Lifetime, LifetimeParam,
+    ParenthesizedGenericArguments, Path, PathArguments, Result, Signature, Stmt, TraitBound, Type,
+    TypeBareFn, TypeImplTrait, TypeParamBound, TypePath, TypeReference, TypeTuple,
 };
 
-use crate::{gen_visit::GenVisitor, parse::AttrGenerator};
+use crate::{dispatch::DispatchVisitor, gen_visit::GenVisitor, parse::AttrGenerator};
 
 impl AttrGenerator {
+    /// Multi-output generators also expand to their outputs struct.
+    /// `maybe_async` functions are expanded twice,
+    /// as the async function and as a blocking function with a `_blocking` suffix
+    pub fn expand(self) -> Result<TokenStream> {
+        if !self.outputs.is_empty() {
+            return self.process_outputs();
+        }
+        if !self.maybe_async {
+            return self.process().map(ToTokens::into_token_stream);
+        }
+        if self.func.sig.asyncness.is_none() {
+            return Err(Error::new_spanned(
+                self.func.sig.fn_token,
+                "`maybe_async` functions must be declared `async`",
+            ));
+        }
+
+        let mut blocking = self.clone();
+        let sig = &mut blocking.func.sig;
+        sig.asyncness = None;
+        sig.ident = format_ident!("{}_blocking", sig.ident);
+
+        let asynch = self.process()?;
+        let blocking = blocking.process()?;
+        Ok(quote! { #asynch #blocking })
+    }
+
     pub fn process(mut self) -> Result<ItemFn> {
         let ItemFn { sig, block, .. } = &mut self.func;
         let return_ty = Self::take_return_ty(sig);
+        let captures = capture_lifetimes(sig);
         let sync = sig.asyncness.take().is_none();
 
         let yield_ty = if self.yields {
@@ -30,32 +62,308 @@ impl AttrGenerator {
         } else {
             new_path! { ::core::convert::Infallible }
         };
-        let return_ty = if self.fallible {
+        let throw_ty = if self.fallible {
+            create_break_type(&return_ty)
+        } else {
+            path_type(new_path! { ::core::convert::Infallible })
+        };
+        if self.clone && (self.lending || self.resumes.is_some() || (sync && !self.yields)) {
+            return Err(Error::new(
+                Span::call_site(),
+                "`clone` requires `async` or `yields`, without `lending` or `resumes`",
+            ));
+        }
+        if self.dispatch
+            && (self.lending
+                || self.resumes.is_some()
+                || self.yield_ty.is_some()
+                || self.clone
+                || self.movable
+                || self.maybe_async)
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`dispatch` cannot be used with `lending`, `resumes`, `clone`, `movable`, `maybe_async` or a final value",
+            ));
+        }
+        if self.recoverable
+            && (!self.yields
+                || self.lending
+                || self.resumes.is_some()
+                || self.yield_ty.is_some()
+                || self.dispatch)
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`fallible(recoverable)` requires the `yields` effect, without `lending`, `resumes`, `dispatch` or a final value",
+            ));
+        }
+        if self.uses.is_some()
+            && (self.lending
+                || self.resumes.is_some()
+                || self.yield_ty.is_some()
+                || self.clone
+                || self.dispatch
+                || self.recoverable)
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`uses` cannot be used with `lending`, `resumes`, `clone`, `dispatch`, `fallible(recoverable)` or a final value",
+            ));
+        }
+        if self.cancellable
+            && (sync
+                || self.lending
+                || self.resumes.is_some()
+                || self.yield_ty.is_some()
+                || self.clone
+                || self.dispatch
+                || self.maybe_async
+                || self.uses.is_some())
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`cancellable` requires `async`, without `lending`, `resumes`, `clone`, `dispatch`, `maybe_async`, `uses` or a final value",
+            ));
+        }
+        if self.budget.is_some()
+            && (sync
+                || !self.yields
+                || self.lending
+                || self.resumes.is_some()
+                || self.clone
+                || self.dispatch
+                || self.maybe_async
+                || self.uses.is_some()
+                || self.cancellable)
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`budget` requires `async` and the `yields` effect, without `lending`, `resumes`, `clone`, `dispatch`, `maybe_async`, `uses` or `cancellable`",
+            ));
+        }
+        if self.lending {
+            if !self.yields || self.yield_ty.is_some() || self.resumes.is_some() || self.movable {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`lending` requires the `yields` effect, without `resumes`, `movable` or a final value",
+                ));
+            }
+            let lend_ty = create_lend_type(&return_ty, self.fallible)?;
+            // the lent reference has no lifetime to name in the signature, but the failure doesn't depend on it
+            let fallible_ty = if self.fallible {
+                create_fallible_path(&static_references(&return_ty))
+            } else {
+                fallible_ty
+            };
+            let mut lending = new_path!(::jenner::LendingEffective);
+            lending.segments.last_mut().unwrap().arguments = angle_bracketed([
+                assoc_type("Lend", lend_ty),
+                assoc_type("Failure", path_type(fallible_ty)),
+                assoc_type("Async", path_type(async_ty)),
+            ]);
+            sig.output = syn::ReturnType::Type(
+                Default::default(),
+                Box::new(Type::ImplTrait(TypeImplTrait {
+                    impl_token: Default::default(),
+                    bounds: [trait_bound(lending)].into_iter().chain(captures).collect(),
+                })),
+            );
+
+            let mut visitor = GenVisitor::new(sync, true, self.fallible);
+            visitor.park = sync && self.maybe_async;
+            visitor.lending = true;
+            block.stmts = vec![Stmt::Expr(visitor.into_generator(&mut block.stmts), None)];
+            return Ok(self.func);
+        }
+
+        // recoverable generators yield the errors along with the items
+        let return_ty = if self.fallible && !self.recoverable {
             create_fallible_return_type(return_ty)
         } else {
             return_ty
         };
+        // with `yields = Item`, the return type becomes the final value of the generator
+        let (return_ty, output_ty) = match self.yield_ty.take() {
+            Some(yield_ty) => (yield_ty, Some(return_ty)),
+            None => (return_ty, None),
+        };
+
+        let mut bounds = vec![];
+        let (effective, extra) = if let Some(ops) = self.uses.clone() {
+            (new_path!(::jenner::Uses), vec![assoc_type("Ops", ops)])
+        } else if self.cancellable {
+            (new_path!(::jenner::Cancellable), vec![])
+        } else if let Some(resume_ty) = self.resumes.clone() {
+            if !self.yields {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`resumes` requires the `yields` effect",
+                ));
+            }
+            if let Some(output_ty) = output_ty {
+                let mut effective_return = new_path!(::jenner::EffectiveReturn);
+                effective_return.segments.last_mut().unwrap().arguments =
+                    angle_bracketed([assoc_type("Output", output_ty)]);
+                bounds.push(trait_bound(effective_return));
+            }
+            (
+                new_path!(::jenner::EffectiveResume),
+                vec![
+                    assoc_type("Resume", resume_ty),
+                    assoc_type("Throw", throw_ty.clone()),
+                ],
+            )
+        } else if let Some(output_ty) = output_ty {
+            (
+                new_path!(::jenner::EffectiveReturn),
+                vec![assoc_type("Output", output_ty)],
+            )
+        } else {
+            (new_path!(::jenner::effective::Effective), vec![])
+        };
+        bounds.insert(
+            0,
+            trait_bound(create_impl_effective(
+                effective,
+                extra,
+                return_ty,
+                fallible_ty.clone(),
+                yield_ty,
+                async_ty.clone(),
+            )),
+        );
+
+        if self.clone {
+            bounds.push(trait_bound(new_path!(::core::clone::Clone)));
+        }
+        if self.movable {
+            bounds.push(trait_bound(new_path!(::core::marker::Unpin)));
+        }
+        bounds.extend(captures);
 
         sig.output = syn::ReturnType::Type(
             Default::default(),
             Box::new(Type::ImplTrait(TypeImplTrait {
                 impl_token: Default::default(),
-                bounds: [TypeParamBound::Trait(TraitBound {
-                    paren_token: None,
-                    modifier: syn::TraitBoundModifier::None,
-                    lifetimes: None,
-                    path: create_impl_effective(return_ty, fallible_ty, yield_ty, async_ty),
-                })]
-                .into_iter()
-                .collect(),
+                bounds: bounds.into_iter().collect(),
             })),
         );
 
-        let visitor = GenVisitor::new(sync, self.yields, self.fallible);
+        if self.dispatch {
+            // the body chooses an effect up front, it is not a generator
+            let failure = path_type(fallible_ty);
+            let async_ = path_type(async_ty);
+            block.stmts = DispatchVisitor::new(self.yields, failure, async_).into_dispatch(block);
+            return Ok(self.func);
+        }
+
+        let resumes = self.resumes.map(|resume_ty| (resume_ty, throw_ty));
+        let mut visitor = GenVisitor::new(sync, self.yields, self.fallible);
+        visitor.park = sync && self.maybe_async;
+        visitor.resumes = resumes;
+        // static generators can never be cloned
+        visitor.movable = self.movable || self.clone;
+        visitor.clone = self.clone;
+        visitor.recoverable = self.recoverable;
+        visitor.uses = self.uses;
+        visitor.cancellable = self.cancellable;
+        visitor.budget = self.budget;
         block.stmts = vec![Stmt::Expr(visitor.into_generator(&mut block.stmts), None)];
         Ok(self.func)
     }
 
+    /// Multi-output generators yield an enum of all the outputs,
+    /// which is routed to a struct of outputs that share the generator
+    fn process_outputs(mut self) -> Result<TokenStream> {
+        if self.lending
+            || self.resumes.is_some()
+            || self.clone
+            || self.dispatch
+            || self.maybe_async
+            || self.recoverable
+            || self.uses.is_some()
+            || self.cancellable
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "multi-output generators cannot be used with `lending`, `resumes`, `clone`, `dispatch`, `maybe_async`, `uses`, `cancellable` or `fallible(recoverable)`",
+            ));
+        }
+        let ItemFn { sig, block, .. } = &mut self.func;
+        if sig.asyncness.take().is_none() {
+            return Err(Error::new_spanned(
+                sig.fn_token,
+                "multi-output generators must be `async`",
+            ));
+        }
+        let return_ty = Self::take_return_ty(sig);
+        let captures = capture_lifetimes(sig);
+        let fallible_ty = if self.fallible {
+            create_fallible_path(&return_ty)
+        } else {
+            new_path! { ::core::convert::Infallible }
+        };
+
+        let name = upper_camel_case(&sig.ident);
+        let item = format_ident!("{}Item", name);
+        let outputs = format_ident!("{}Outputs", name);
+        let doc = format!("The outputs of [`{}`]", sig.ident);
+        let names: Vec<_> = self.outputs.iter().map(|output| &output.name).collect();
+        let tys: Vec<_> = self.outputs.iter().map(|output| &output.ty).collect();
+        let len = names.len();
+        let indices: Vec<_> = (0..len).collect();
+
+        let effective = create_impl_effective(
+            new_path!(::jenner::effective::Effective),
+            vec![],
+            path_type(item.clone().into()),
+            fallible_ty,
+            new_path! { ::jenner::effective::Multiple },
+            new_path! { ::jenner::effective::Async },
+        );
+        sig.output = parse_quote! { -> #outputs<impl #effective #(+ #captures)*> };
+
+        let mut visitor = GenVisitor::new(false, true, self.fallible);
+        visitor.outputs = Some(item.clone());
+        visitor.movable = self.movable;
+        visitor.budget = self.budget;
+        let generator = visitor.into_generator(&mut block.stmts);
+        block.stmts = parse_quote! {
+            let __shared__ = ::jenner::__private::Demux::new(
+                #generator,
+                |item| match item { #(#item::#names(_) => #indices,)* },
+                #len,
+            );
+            #outputs {
+                #(#names: ::jenner::DemuxOutput::new(&__shared__, #indices, |item| {
+                    #[allow(unreachable_patterns)]
+                    match item {
+                        #item::#names(x) => x,
+                        _ => ::core::unreachable!(),
+                    }
+                }),)*
+            }
+        };
+
+        let func = &self.func;
+        let vis = &func.vis;
+        Ok(quote! {
+            #[doc = #doc]
+            #vis struct #outputs<S: ::jenner::effective::Effective> {
+                #(#vis #names: ::jenner::DemuxOutput<S, #tys>,)*
+            }
+
+            #[doc(hidden)]
+            #[allow(non_camel_case_types)]
+            #vis enum #item {
+                #(#names(#tys),)*
+            }
+
+            #func
+        })
+    }
+
     fn take_return_ty(sig: &mut Signature) -> Type {
         match mem::replace(&mut sig.output, syn::ReturnType::Default) {
             syn::ReturnType::Default => Type::Tuple(TypeTuple {
@@ -67,9 +375,157 @@ impl AttrGenerator {
     }
 }
 
-fn create_fallible_path(return_ty: &Type) -> Path {
+/// `impl Trait` return types only capture the lifetimes that appear in their bounds,
+/// but the generator captures every argument. This names the elided lifetimes of the arguments,
+/// and returns a `Captures<'a>` bound for each lifetime parameter.
+fn capture_lifetimes(sig: &mut Signature) -> Vec<TypeParamBound> {
+    struct NameElided {
+        named: Vec<Lifetime>,
+    }
+
+    impl NameElided {
+        fn fresh(&mut self) -> Lifetime {
+            let lifetime = Lifetime::new(&format!("'__arg{}", self.named.len()), Span::call_site());
+            self.named.push(lifetime.clone());
+            lifetime
+        }
+    }
+
+    impl VisitMut for NameElided {
+        fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+            if i.lifetime.is_none() {
+                i.lifetime = Some(self.fresh());
+            }
+            visit_mut::visit_type_reference_mut(self, i);
+        }
+
+        fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+            if i.ident == "_" {
+                *i = self.fresh();
+            }
+        }
+
+        // elided lifetimes in fn pointers and `Fn` traits belong to them, not to the function
+        fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+        fn visit_parenthesized_generic_arguments_mut(
+            &mut self,
+            _: &mut ParenthesizedGenericArguments,
+        ) {
+        }
+    }
+
+    let mut visitor = NameElided { named: vec![] };
+    for input in &mut sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => match &mut receiver.reference {
+                Some((_, lifetime @ None)) => {
+                    let named = visitor.fresh();
+                    let mutability = receiver.mutability;
+                    receiver.ty = parse_quote! { &#named #mutability Self };
+                    *lifetime = Some(named);
+                }
+                Some(_) => {}
+                None => visitor.visit_type_mut(&mut receiver.ty),
+            },
+            FnArg::Typed(arg) => visitor.visit_type_mut(&mut arg.ty),
+        }
+    }
+
+    // lifetime parameters come before the others
+    let lifetimes = sig.generics.lifetimes().count();
+    for (i, lifetime) in visitor.named.into_iter().enumerate() {
+        sig.generics.params.insert(
+            lifetimes + i,
+            GenericParam::Lifetime(LifetimeParam::new(lifetime)),
+        );
+    }
+    sig.generics
+        .lifetimes()
+        .map(|param| {
+            let lifetime = &param.lifetime;
+            parse_quote! { ::jenner::__private::Captures<#lifetime> }
+        })
+        .collect()
+}
+
+/// `split_frames` becomes `SplitFrames`
+fn upper_camel_case(ident: &Ident) -> String {
+    ident
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+        })
+        .collect()
+}
+
+/// Lending generators yield references, which are erased to pointers while they are lent.
+/// The pointer type is determined from the reference in the return type.
+fn create_lend_type(return_ty: &Type, fallible: bool) -> Result<Type> {
+    let error = || {
+        Error::new_spanned(
+            return_ty,
+            "lending generators must return a reference type, eg `&[u8]`",
+        )
+    };
+    let item_ty = if fallible {
+        // `Result<&T, E>`
+        let Type::Path(path) = return_ty else {
+            return Err(error());
+        };
+        let segment = path.path.segments.last().ok_or_else(error)?;
+        let PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return Err(error());
+        };
+        match args.args.first() {
+            Some(GenericArgument::Type(ty)) => ty,
+            _ => return Err(error()),
+        }
+    } else {
+        return_ty
+    };
+
+    match item_ty {
+        Type::Reference(reference) => {
+            let elem = &reference.elem;
+            Ok(if reference.mutability.is_some() {
+                parse_quote! { *mut #elem }
+            } else {
+                parse_quote! { *const #elem }
+            })
+        }
+        _ => Err(error()),
+    }
+}
+
+/// `Result<&[u8], E>` becomes `Result<&'static [u8], E>`
+fn static_references(ty: &Type) -> Type {
+    struct StaticReferences;
+
+    impl VisitMut for StaticReferences {
+        fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+            i.lifetime.get_or_insert_with(|| parse_quote! { 'static });
+            visit_mut::visit_type_reference_mut(self, i);
+        }
+
+        fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+            if i.ident == "_" {
+                *i = parse_quote! { 'static };
+            }
+        }
+    }
+
+    let mut ty = ty.clone();
+    StaticReferences.visit_type_mut(&mut ty);
+    ty
+}
+
+fn create_break_type(return_ty: &Type) -> Type {
     let simple_try = new_path!(::jenner::effective::SimpleTry::Break);
-    let failure = TypePath {
+    Type::Path(TypePath {
         qself: Some(syn::QSelf {
             lt_token: Default::default(),
             ty: Box::new(return_ty.clone()),
@@ -78,21 +534,17 @@ fn create_fallible_path(return_ty: &Type) -> Path {
             gt_token: Default::default(),
         }),
         path: simple_try,
-    };
+    })
+}
+
+fn create_fallible_path(return_ty: &Type) -> Path {
     let mut path = new_path! { ::jenner::effective::Failure };
     path.segments.last_mut().unwrap().arguments =
-        PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
-            colon2_token: None,
-            lt_token: Default::default(),
-            args: [GenericArgument::Type(failure.into())]
-                .into_iter()
-                .collect(),
-            gt_token: Default::default(),
-        });
+        angle_bracketed([GenericArgument::Type(create_break_type(return_ty))]);
     path
 }
 
-fn create_fallible_return_type(return_ty: Type) -> Type {
+pub fn create_fallible_return_type(return_ty: Type) -> Type {
     let simple_try = new_path!(::jenner::effective::SimpleTry::Continue);
     let failure = TypePath {
         qself: Some(syn::QSelf {
@@ -109,54 +561,49 @@ fn create_fallible_return_type(return_ty: Type) -> Type {
 }
 
 fn create_impl_effective(
+    mut effective: Path,
+    extra: Vec<GenericArgument>,
     return_ty: Type,
     fallible_ty: Path,
     yield_ty: Path,
     async_ty: Path,
 ) -> Path {
-    let mut effective = new_path!(::jenner::effective::Effective);
-    effective.segments.last_mut().unwrap().arguments =
-        PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
-            colon2_token: None,
-            lt_token: Default::default(),
-            args: [
-                GenericArgument::AssocType(AssocType {
-                    ident: format_ident!("Item"),
-                    generics: None,
-                    eq_token: Default::default(),
-                    ty: return_ty,
-                }),
-                GenericArgument::AssocType(AssocType {
-                    ident: format_ident!("Failure"),
-                    generics: None,
-                    eq_token: Default::default(),
-                    ty: Type::Path(TypePath {
-                        qself: None,
-                        path: fallible_ty,
-                    }),
-                }),
-                GenericArgument::AssocType(AssocType {
-                    ident: format_ident!("Produces"),
-                    generics: None,
-                    eq_token: Default::default(),
-                    ty: Type::Path(TypePath {
-                        qself: None,
-                        path: yield_ty,
-                    }),
-                }),
-                GenericArgument::AssocType(AssocType {
-                    ident: format_ident!("Async"),
-                    generics: None,
-                    eq_token: Default::default(),
-                    ty: Type::Path(TypePath {
-                        qself: None,
-                        path: async_ty,
-                    }),
-                }),
-            ]
-            .into_iter()
-            .collect(),
-            gt_token: Default::default(),
-        });
+    effective.segments.last_mut().unwrap().arguments = angle_bracketed(extra.into_iter().chain([
+        assoc_type("Item", return_ty),
+        assoc_type("Failure", path_type(fallible_ty)),
+        assoc_type("Produces", path_type(yield_ty)),
+        assoc_type("Async", path_type(async_ty)),
+    ]));
     effective
 }
+
+fn assoc_type(ident: &str, ty: Type) -> GenericArgument {
+    GenericArgument::AssocType(AssocType {
+        ident: format_ident!("{}", ident),
+        generics: None,
+        eq_token: Default::default(),
+        ty,
+    })
+}
+
+fn angle_bracketed(args: impl IntoIterator<Item = GenericArgument>) -> PathArguments {
+    PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
+        colon2_token: None,
+        lt_token: Default::default(),
+        args: args.into_iter().collect(),
+        gt_token: Default::default(),
+    })
+}
+
+fn path_type(path: Path) -> Type {
+    Type::Path(TypePath { qself: None, path })
+}
+
+fn trait_bound(path: Path) -> TypeParamBound {
+    TypeParamBound::Trait(TraitBound {
+        paren_token: None,
+        modifier: syn::TraitBoundModifier::None,
+        lifetimes: None,
+        path,
+    })
+}
@@ -8,24 +8,31 @@ use std::{
 
 use effective::{Async, EffectResult, Effective, EffectiveResult, Failure, Multiple, Single};
 
+use crate::EffectiveReturn;
+
 pin_project_lite::pin_project!(
     #[doc(hidden)]
-    pub struct AsyncGeneratorImpl<G> {
+    #[derive(Clone)]
+    pub struct AsyncGeneratorImpl<G, R> {
         #[pin]
         generator: G,
+        output: Option<R>,
     }
 );
 
 pin_project_lite::pin_project!(
     #[doc(hidden)]
-    pub struct AsyncFallibleGeneratorImpl<G> {
+    #[derive(Clone)]
+    pub struct AsyncFallibleGeneratorImpl<G, R> {
         #[pin]
         generator: G,
+        output: Option<R>,
     }
 );
 
 pin_project_lite::pin_project!(
     #[doc(hidden)]
+    #[derive(Clone)]
     pub struct AsyncImpl<G> {
         #[pin]
         generator: G,
@@ -34,6 +41,7 @@ pin_project_lite::pin_project!(
 
 pin_project_lite::pin_project!(
     #[doc(hidden)]
+    #[derive(Clone)]
     pub struct AsyncFallibleImpl<G> {
         #[pin]
         generator: G,
@@ -58,31 +66,71 @@ impl<'a> From<&mut Context<'a>> for UnsafeContextRef {
 
 unsafe impl Send for UnsafeContextRef {}
 
-impl<G> AsyncGeneratorImpl<G> {
+impl<G, R> AsyncGeneratorImpl<G, R> {
+    /// Creates the effect without hiding its type, so that it can be cloned
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            output: None,
+        }
+    }
+
     #[doc(hidden)]
     pub fn create<Y>(
         generator: G,
-    ) -> impl Effective<Item = Y, Produces = Multiple, Failure = Infallible, Async = Async>
+    ) -> impl EffectiveReturn<
+        Output = R,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Infallible,
+        Async = Async,
+    >
     where
-        G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = ()>,
+        G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = R>,
     {
-        Self { generator }
+        Self {
+            generator,
+            output: None,
+        }
     }
 }
 
-impl<G> AsyncFallibleGeneratorImpl<G> {
+impl<G, R> AsyncFallibleGeneratorImpl<G, R> {
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            output: None,
+        }
+    }
+
     #[doc(hidden)]
     pub fn create<Y, E>(
         generator: G,
-    ) -> impl Effective<Item = Y, Produces = Multiple, Failure = Failure<E>, Async = Async>
+    ) -> impl EffectiveReturn<
+        Output = R,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Failure<E>,
+        Async = Async,
+    >
     where
-        G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = Result<(), E>>,
+        G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = Result<R, E>>,
     {
-        Self { generator }
+        Self {
+            generator,
+            output: None,
+        }
     }
 }
 
 impl<G> AsyncImpl<G> {
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self { generator }
+    }
+
     #[doc(hidden)]
     pub fn create<Y>(
         generator: G,
@@ -95,6 +143,11 @@ impl<G> AsyncImpl<G> {
 }
 
 impl<G> AsyncFallibleImpl<G> {
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self { generator }
+    }
+
     #[doc(hidden)]
     pub fn create<Y, E>(
         generator: G,
@@ -106,9 +159,9 @@ impl<G> AsyncFallibleImpl<G> {
     }
 }
 
-impl<Y, G> Effective for AsyncGeneratorImpl<G>
+impl<Y, R, G> Effective for AsyncGeneratorImpl<G, R>
 where
-    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = ()>,
+    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = R>,
 {
     type Item = Y;
     type Failure = Infallible;
@@ -116,17 +169,32 @@ where
     type Async = Async;
 
     fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
-        match self.project().generator.resume(cx.into()) {
+        let this = self.project();
+        match this.generator.resume(cx.into()) {
             GeneratorState::Yielded(Poll::Ready(x)) => EffectResult::Item(x),
             GeneratorState::Yielded(Poll::Pending) => EffectResult::Pending(Async),
-            GeneratorState::Complete(()) => EffectResult::Done(Multiple),
+            GeneratorState::Complete(r) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
         }
     }
 }
 
-impl<Y, E, G> Effective for AsyncFallibleGeneratorImpl<G>
+impl<Y, R, G> EffectiveReturn for AsyncGeneratorImpl<G, R>
 where
-    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = Result<(), E>>,
+    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = R>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
+impl<Y, R, E, G> Effective for AsyncFallibleGeneratorImpl<G, R>
+where
+    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = Result<R, E>>,
 {
     type Item = Y;
     type Failure = Failure<E>;
@@ -134,15 +202,30 @@ where
     type Async = Async;
 
     fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
-        match self.project().generator.resume(cx.into()) {
+        let this = self.project();
+        match this.generator.resume(cx.into()) {
             GeneratorState::Yielded(Poll::Ready(x)) => EffectResult::Item(x),
             GeneratorState::Yielded(Poll::Pending) => EffectResult::Pending(Async),
             GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
-            GeneratorState::Complete(Ok(())) => EffectResult::Done(Multiple),
+            GeneratorState::Complete(Ok(r)) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
         }
     }
 }
 
+impl<Y, R, E, G> EffectiveReturn for AsyncFallibleGeneratorImpl<G, R>
+where
+    G: Generator<UnsafeContextRef, Yield = Poll<Y>, Return = Result<R, E>>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
 impl<Y, G> Effective for AsyncImpl<G>
 where
     G: Generator<UnsafeContextRef, Yield = Poll<Infallible>, Return = Y>,
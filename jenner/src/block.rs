@@ -0,0 +1,230 @@
+use std::{
+    convert::Infallible,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Wake, Waker},
+    thread::{self, Thread},
+    time::Instant,
+};
+
+use effective::{EffectResult, Effective, EffectiveResult, Failure, Multiple, Single};
+
+/// Wakes the thread that is blocked on an effect
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls the effect until it is no longer pending, parking the current thread in between.
+///
+/// Used by for-loops in the blocking variant of a `maybe_async` body
+#[doc(hidden)]
+pub fn park_on<E: Effective + ?Sized>(effect: Pin<&mut E>) -> EffectiveResult<E> {
+    match park_until(effect, None) {
+        Some(result) => result,
+        None => unreachable!(),
+    }
+}
+
+/// Used by `.await` in the blocking variant of a `maybe_async` body.
+/// Fails to compile if the effect produces several items.
+#[doc(hidden)]
+pub fn await_parked<E>(effect: Pin<&mut E>) -> Result<E::Item, E::Failure>
+where
+    E: Effective<Produces = Single> + ?Sized,
+{
+    match park_on(effect) {
+        EffectResult::Item(x) => Ok(x),
+        EffectResult::Failure(x) => Err(x),
+        EffectResult::Done(_) | EffectResult::Pending(_) => unreachable!(),
+    }
+}
+
+/// Polls the effect like [`park_on`], giving up once the deadline has passed
+pub fn park_until<E: Effective + ?Sized>(
+    mut effect: Pin<&mut E>,
+    deadline: Option<Instant>,
+) -> Option<EffectiveResult<E>> {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match effect.as_mut().poll_effect(&mut cx) {
+            EffectResult::Pending(_) => match deadline {
+                None => thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+            },
+            result => return Some(result),
+        }
+    }
+}
+
+/// Runs a [`Single`] effect to completion on the current thread, without an async runtime.
+///
+/// The thread is parked while the effect is pending, until its waker is woken.
+/// Effects that rely on a runtime, like tokio's timers and sockets, still need that runtime to be running.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use futures::channel::oneshot;
+/// use jenner::effect;
+/// use std::{thread, time::Duration};
+///
+/// #[effect]
+/// async fn double(rx: oneshot::Receiver<u32>) -> u32 {
+///     rx.await.unwrap() * 2
+/// }
+///
+/// fn main() {
+///     let (tx, rx) = oneshot::channel();
+///     thread::spawn(move || {
+///         thread::sleep(Duration::from_millis(10));
+///         tx.send(21).unwrap();
+///     });
+///     // parks until the other thread sends the value
+///     assert_eq!(jenner::block_on(double(rx)), 42);
+/// }
+/// ```
+pub fn block_on<E>(effect: E) -> E::Item
+where
+    E: Effective<Produces = Single, Failure = Infallible>,
+{
+    match park_on(pin!(effect)) {
+        EffectResult::Item(x) => x,
+        EffectResult::Failure(x) => match x {},
+        EffectResult::Done(_) | EffectResult::Pending(_) => unreachable!(),
+    }
+}
+
+/// Runs a fallible [`Single`] effect to completion on the current thread, like [`block_on`].
+///
+/// Returns `Err(e)` if the effect failed.
+pub fn try_block_on<E, Err>(effect: E) -> Result<E::Item, Err>
+where
+    E: Effective<Produces = Single, Failure = Failure<Err>>,
+{
+    match park_on(pin!(effect)) {
+        EffectResult::Item(x) => Ok(x),
+        EffectResult::Failure(Failure(e)) => Err(e),
+        EffectResult::Done(_) | EffectResult::Pending(_) => unreachable!(),
+    }
+}
+
+/// Turns a [`Multiple`] effect into an [`Iterator`], parking the current thread while it is pending.
+///
+/// This lets synchronous code consume async generators.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use jenner::effect;
+///
+/// #[effect(yields)]
+/// async fn countdown(n: u32) -> u32 {
+///     for i in (0..n).rev() {
+///         jenner::yield_now().await;
+///         yield i;
+///     }
+/// }
+///
+/// fn main() {
+///     let v: Vec<u32> = jenner::block_iter(countdown(3)).collect();
+///     assert_eq!(v, [2, 1, 0]);
+/// }
+/// ```
+pub fn block_iter<E>(effect: E) -> BlockIter<E>
+where
+    E: Effective<Produces = Multiple, Failure = Infallible>,
+{
+    BlockIter {
+        effect: Box::pin(effect),
+        done: false,
+    }
+}
+
+/// Turns a fallible [`Multiple`] effect into an [`Iterator`], like [`block_iter`].
+///
+/// A failure is returned as the last item, `Err(e)`.
+pub fn try_block_iter<E, Err>(effect: E) -> TryBlockIter<E>
+where
+    E: Effective<Produces = Multiple, Failure = Failure<Err>>,
+{
+    TryBlockIter {
+        effect: Box::pin(effect),
+        done: false,
+    }
+}
+
+/// Iterator returned by [`block_iter`]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct BlockIter<E> {
+    effect: Pin<Box<E>>,
+    done: bool,
+}
+
+/// Iterator returned by [`try_block_iter`]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TryBlockIter<E> {
+    effect: Pin<Box<E>>,
+    done: bool,
+}
+
+impl<E> Iterator for BlockIter<E>
+where
+    E: Effective<Produces = Multiple, Failure = Infallible>,
+{
+    type Item = E::Item;
+
+    fn next(&mut self) -> Option<E::Item> {
+        if self.done {
+            return None;
+        }
+        match park_on(self.effect.as_mut()) {
+            EffectResult::Item(x) => Some(x),
+            EffectResult::Failure(x) => match x {},
+            EffectResult::Done(Multiple) => {
+                self.done = true;
+                None
+            }
+            EffectResult::Pending(_) => unreachable!(),
+        }
+    }
+}
+
+impl<E, Err> Iterator for TryBlockIter<E>
+where
+    E: Effective<Produces = Multiple, Failure = Failure<Err>>,
+{
+    type Item = Result<E::Item, Err>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match park_on(self.effect.as_mut()) {
+            EffectResult::Item(x) => Some(Ok(x)),
+            EffectResult::Failure(Failure(e)) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            EffectResult::Done(Multiple) => {
+                self.done = true;
+                None
+            }
+            EffectResult::Pending(_) => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    panic,
+    pin::{pin, Pin},
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+use effective::{
+    Async, Blocking, EffectResult, Effective, EffectiveResult, Failure, Multiple, Single,
+};
+
+use crate::{demux::lock, lift::blocking, noop::noop_waker};
+
+/// Runs a blocking [`Multiple`] effect, like a sync `#[effect(yields)]` generator,
+/// on a thread of its own and produces its items asynchronously.
+///
+/// Up to `capacity` items are buffered, after which the thread waits for them to be consumed
+/// before resuming the effect. Dropping the returned effect stops the thread at its next item.
+/// If the effect panics, the panic is resumed where the items are consumed.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::{Async, Effective, EffectiveExt, Multiple};
+/// use jenner::effect;
+/// use std::convert::Infallible;
+///
+/// #[effect(yields)]
+/// fn parse(input: String) -> u32 {
+///     // expensive work that would hold up the async tasks on this thread
+///     for line in input.lines() {
+///         yield line.parse().unwrap();
+///     }
+/// }
+///
+/// #[effect]
+/// async fn sum(
+///     input: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+/// ) -> u32 {
+///     let mut total = 0;
+///     #[effect(async)]
+///     for x in input {
+///         total += x;
+///     }
+///     total
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let numbers = jenner::offload(parse("1\n2\n3".to_owned()), 16);
+///     assert_eq!(sum(numbers).shim().await, 6);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0, or if the thread cannot be spawned.
+pub fn offload<E>(effect: E, capacity: usize) -> Offload<E::Item, E::Failure>
+where
+    E: Effective<Produces = Multiple, Async = Blocking> + Send + 'static,
+    E::Item: Send + 'static,
+    E::Failure: Send + 'static,
+{
+    let channel = Channel::new(capacity);
+    let producer = Producer(channel.clone());
+    let thread = thread::Builder::new()
+        .name("jenner-offload".to_owned())
+        .spawn(move || {
+            let mut effect = pin!(effect);
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            // only resume the effect once there is room for its next item
+            while producer.0.ready() {
+                match effect.as_mut().poll_effect(&mut cx) {
+                    EffectResult::Item(x) => producer.0.push(x),
+                    EffectResult::Failure(failure) => {
+                        return producer.0.finish(End::Failure(failure))
+                    }
+                    EffectResult::Done(_) => return producer.0.finish(End::Done),
+                    EffectResult::Pending(p) => blocking(p),
+                }
+            }
+        })
+        .expect("failed to spawn the offload thread");
+    Offload {
+        consumer: Consumer(channel),
+        thread: Some(thread),
+    }
+}
+
+/// Drives an async [`Multiple`] effect and sends its items to an [`Iterator`],
+/// so that they can be consumed on a plain thread.
+///
+/// The returned [`Pump`] has to be run, for example spawned on the async runtime the effect needs,
+/// while the [`Pumped`] iterator is moved to the consuming thread.
+/// Up to `capacity` items are buffered, after which the pump waits for them to be consumed.
+/// The pump completes once the effect has, or once the iterator is dropped.
+/// If the pump is dropped first, for example when its task is cancelled, the iterator ends early.
+///
+/// The iterator produces the items of an infallible effect, or `Result`s of a fallible one,
+/// ending with the failure.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::time::Duration;
+///
+/// #[effect(yields)]
+/// async fn ticks(n: u32) -> u32 {
+///     for i in 0..n {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         yield i;
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (pump, ticks) = jenner::pump(ticks(3), 1);
+///     let consumer = std::thread::spawn(move || ticks.collect::<Vec<_>>());
+///     pump.shim().await;
+///     assert_eq!(consumer.join().unwrap(), [0, 1, 2]);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn pump<E>(effect: E, capacity: usize) -> (Pump<E>, Pumped<E::Item, E::Failure>)
+where
+    E: Effective<Produces = Multiple>,
+{
+    let channel = Channel::new(capacity);
+    let pump = Pump {
+        effect,
+        next: None,
+        producer: Producer(channel.clone()),
+    };
+    (pump, Pumped(Consumer(channel)))
+}
+
+/// Effect returned by [`offload`]
+#[must_use = "effects do nothing unless awaited"]
+pub struct Offload<T, F> {
+    consumer: Consumer<T, F>,
+    thread: Option<JoinHandle<()>>,
+}
+
+pin_project_lite::pin_project!(
+    /// Effect returned by [`pump`], which sends the items of the effect to the [`Pumped`] iterator
+    #[must_use = "effects do nothing unless awaited"]
+    pub struct Pump<E: Effective> {
+        #[pin]
+        effect: E,
+        next: Option<E::Item>,
+        producer: Producer<E::Item, E::Failure>,
+    }
+);
+
+/// Iterator returned by [`pump`]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Pumped<T, F>(Consumer<T, F>);
+
+/// How the iterator of [`pump`] produces the items and failure of the effect
+#[doc(hidden)]
+pub trait PumpedItem<T> {
+    type Output;
+    fn item(item: T) -> Self::Output;
+    fn failure(self) -> Self::Output;
+}
+
+impl<T> PumpedItem<T> for Infallible {
+    type Output = T;
+    fn item(item: T) -> T {
+        item
+    }
+    fn failure(self) -> T {
+        match self {}
+    }
+}
+
+impl<T, E> PumpedItem<T> for Failure<E> {
+    type Output = Result<T, E>;
+    fn item(item: T) -> Result<T, E> {
+        Ok(item)
+    }
+    fn failure(self) -> Result<T, E> {
+        Err(self.0)
+    }
+}
+
+impl<T, F> Effective for Offload<T, F> {
+    type Item = T;
+    type Failure = F;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.consumer.0.receive(Some(cx.waker())) {
+            Some(Received::Item(x)) => EffectResult::Item(x),
+            Some(Received::Failure(failure)) => EffectResult::Failure(failure),
+            Some(Received::Done) => EffectResult::Done(Multiple),
+            Some(Received::Dropped) => match self.thread.take().map(JoinHandle::join) {
+                // the thread only stops early if the effect panicked
+                Some(Err(payload)) => panic::resume_unwind(payload),
+                _ => EffectResult::Done(Multiple),
+            },
+            None => EffectResult::Pending(Async),
+        }
+    }
+}
+
+impl<E: Effective<Produces = Multiple>> Effective for Pump<E> {
+    type Item = ();
+    type Failure = Infallible;
+    type Produces = Single;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let mut this = self.project();
+        loop {
+            if let Some(item) = this.next.take() {
+                match this.producer.0.poll_ready(cx) {
+                    Poll::Ready(true) => this.producer.0.push(item),
+                    // nobody is listening anymore
+                    Poll::Ready(false) => return EffectResult::Item(()),
+                    Poll::Pending => {
+                        *this.next = Some(item);
+                        return EffectResult::Pending(Async);
+                    }
+                }
+            }
+            match this.effect.as_mut().poll_effect(cx) {
+                EffectResult::Item(x) => *this.next = Some(x),
+                EffectResult::Failure(failure) => {
+                    this.producer.0.finish(End::Failure(failure));
+                    return EffectResult::Item(());
+                }
+                EffectResult::Done(_) => {
+                    this.producer.0.finish(End::Done);
+                    return EffectResult::Item(());
+                }
+                EffectResult::Pending(_) => return EffectResult::Pending(Async),
+            }
+        }
+    }
+}
+
+impl<T, F: PumpedItem<T>> Iterator for Pumped<T, F> {
+    type Item = F::Output;
+
+    fn next(&mut self) -> Option<F::Output> {
+        match self.0 .0.receive_blocking() {
+            Received::Item(x) => Some(F::item(x)),
+            Received::Failure(failure) => Some(failure.failure()),
+            // the pump was dropped before its effect completed
+            Received::Done | Received::Dropped => None,
+        }
+    }
+}
+
+/// A bounded queue between a producer and a consumer on different threads,
+/// either of which can wait asynchronously or by blocking the thread
+struct Channel<T, F> {
+    state: Mutex<ChannelState<T, F>>,
+    /// Notified when either side makes progress, for the side that is blocking
+    changed: Condvar,
+}
+
+struct ChannelState<T, F> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    /// How the producer stopped, once it has
+    end: Option<End<F>>,
+    /// The consumer was dropped
+    closed: bool,
+    producer: Option<Waker>,
+    consumer: Option<Waker>,
+}
+
+enum End<F> {
+    Done,
+    Failure(F),
+    /// The producer was dropped before the effect completed
+    Dropped,
+}
+
+enum Received<T, F> {
+    Item(T),
+    Failure(F),
+    Done,
+    Dropped,
+}
+
+/// Stops the channel when the producing side goes away
+struct Producer<T, F>(Arc<Channel<T, F>>);
+
+/// Closes the channel when the consuming side goes away
+struct Consumer<T, F>(Arc<Channel<T, F>>);
+
+impl<T, F> Channel<T, F> {
+    fn new(capacity: usize) -> Arc<Self> {
+        assert!(capacity > 0, "the channel must be able to buffer an item");
+        Arc::new(Self {
+            state: Mutex::new(ChannelState {
+                queue: VecDeque::new(),
+                capacity,
+                end: None,
+                closed: false,
+                producer: None,
+                consumer: None,
+            }),
+            changed: Condvar::new(),
+        })
+    }
+
+    fn notify(&self, waker: Option<Waker>) {
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        self.changed.notify_all();
+    }
+
+    /// Blocks until there is room for an item, returning whether the consumer is still there
+    fn ready(&self) -> bool {
+        let mut state = lock(&self.state);
+        while !state.closed && state.queue.len() >= state.capacity {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        !state.closed
+    }
+
+    /// Resolves once there is room for an item, with whether the consumer is still there
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<bool> {
+        let mut state = lock(&self.state);
+        if !state.closed && state.queue.len() >= state.capacity {
+            state.producer = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(!state.closed)
+    }
+
+    fn push(&self, item: T) {
+        let mut state = lock(&self.state);
+        state.queue.push_back(item);
+        let waker = state.consumer.take();
+        drop(state);
+        self.notify(waker);
+    }
+
+    fn finish(&self, end: End<F>) {
+        let mut state = lock(&self.state);
+        if state.end.is_some() {
+            return;
+        }
+        state.end = Some(end);
+        let waker = state.consumer.take();
+        drop(state);
+        self.notify(waker);
+    }
+
+    fn close(&self) {
+        let mut state = lock(&self.state);
+        state.closed = true;
+        state.queue.clear();
+        let waker = state.producer.take();
+        drop(state);
+        self.notify(waker);
+    }
+
+    /// Takes the next item, or how the producer stopped.
+    /// Returns `None` if neither is available yet, registering `waker` if given.
+    fn receive(&self, waker: Option<&Waker>) -> Option<Received<T, F>> {
+        let mut state = lock(&self.state);
+        if let Some(item) = state.queue.pop_front() {
+            // the producer might have been waiting for room
+            let waker = state.producer.take();
+            drop(state);
+            self.notify(waker);
+            return Some(Received::Item(item));
+        }
+        match state.end.take() {
+            Some(End::Failure(failure)) => {
+                state.end = Some(End::Done);
+                Some(Received::Failure(failure))
+            }
+            Some(End::Done) => {
+                state.end = Some(End::Done);
+                Some(Received::Done)
+            }
+            Some(End::Dropped) => {
+                state.end = Some(End::Done);
+                Some(Received::Dropped)
+            }
+            None => {
+                if let Some(waker) = waker {
+                    state.consumer = Some(waker.clone());
+                }
+                None
+            }
+        }
+    }
+
+    /// Blocks until the next item is available, or the producer has stopped
+    fn receive_blocking(&self) -> Received<T, F> {
+        loop {
+            if let Some(received) = self.receive(None) {
+                return received;
+            }
+            let state = lock(&self.state);
+            if state.queue.is_empty() && state.end.is_none() {
+                drop(self.changed.wait(state).unwrap_or_else(|e| e.into_inner()));
+            }
+        }
+    }
+}
+
+impl<T, F> Drop for Producer<T, F> {
+    fn drop(&mut self) {
+        self.0.finish(End::Dropped);
+    }
+}
+
+impl<T, F> Drop for Consumer<T, F> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
@@ -0,0 +1,101 @@
+use std::{pin::Pin, task::Context};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Single};
+
+/// Counts the steps a `#[effect(budget = n)]` generator takes without suspending
+#[doc(hidden)]
+pub struct Budget {
+    steps: u32,
+    remaining: u32,
+}
+
+impl Budget {
+    #[doc(hidden)]
+    pub fn new(steps: u32) -> Self {
+        Self {
+            steps,
+            remaining: steps,
+        }
+    }
+
+    /// Takes a step, returning whether the budget is used up
+    #[doc(hidden)]
+    pub fn step(&mut self) -> bool {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.remaining == 0
+    }
+
+    #[doc(hidden)]
+    pub fn reset(&mut self) {
+        self.remaining = self.steps;
+    }
+}
+
+/// Creates an effect that suspends once, giving the executor a chance to run other tasks.
+///
+/// Awaiting it in a long-running loop keeps the loop from starving the executor,
+/// even if everything else it awaits is always ready. `#[effect(budget = n)]` does this
+/// automatically after `n` steps without suspending.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc,
+/// };
+///
+/// #[effect]
+/// async fn checksum(data: Vec<u8>) -> u32 {
+///     let mut sum = 0u32;
+///     for (i, chunk) in data.chunks(1024).enumerate() {
+///         sum = chunk.iter().fold(sum, |sum, &b| sum.wrapping_mul(31).wrapping_add(b as u32));
+///         if i % 16 == 15 {
+///             jenner::yield_now().await;
+///         }
+///     }
+///     sum
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let ticks = Arc::new(AtomicUsize::new(0));
+///     let counter = ticks.clone();
+///     tokio::spawn(async move {
+///         loop {
+///             counter.fetch_add(1, Ordering::Relaxed);
+///             tokio::task::yield_now().await;
+///         }
+///     });
+///     checksum(vec![1; 100_000]).shim().await;
+///     // the other task ran while the checksum was computed
+///     assert!(ticks.load(Ordering::Relaxed) > 0);
+/// }
+/// ```
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// Effect returned by [`yield_now`]
+#[must_use = "effects do nothing unless awaited"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Effective for YieldNow {
+    type Item = ();
+    type Failure = std::convert::Infallible;
+    type Produces = Single;
+    type Async = Async;
+
+    fn poll_effect(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        if self.yielded {
+            return EffectResult::Item(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        EffectResult::Pending(Async)
+    }
+}
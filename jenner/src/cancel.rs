@@ -0,0 +1,259 @@
+use std::{
+    fmt,
+    ops::{Generator, GeneratorState},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Single};
+
+use crate::{asynch::UnsafeContextRef, uses::Mode};
+
+/// An async effect that can be cancelled while suspended, giving it a chance to clean up.
+///
+/// Created with `#[effect(cancellable)]`. When the effect is cancelled, the `.await` it is
+/// suspended on runs the `jenner::on_cancel!` blocks in scope, most recent first, and the
+/// generator stops. The locals declared after an `on_cancel!` block are dropped before it runs.
+/// Marking the await with `#[effect(cancellable)]` instead makes it return
+/// `Err(Cancelled)`, so the body can handle the cancellation itself.
+///
+/// The cleanup can `.await`, and is run to completion by [`cancel`](Cancellable::cancel)
+/// unless the deadline elapses first. Cancellation is only delivered once,
+/// so awaits during the cleanup are not interrupted.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::{wrappers::future, EffectiveExt};
+/// use jenner::{effect, Cancellable};
+/// use std::{pin::pin, sync::Mutex, time::Duration};
+///
+/// #[effect(cancellable)]
+/// async fn session(log: &Mutex<Vec<&'static str>>) {
+///     log.lock().unwrap().push("hello");
+///     jenner::on_cancel! {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         log.lock().unwrap().push("goodbye");
+///     }
+///     tokio::time::sleep(Duration::from_secs(60)).await;
+///     log.lock().unwrap().push("timed out");
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let log = Mutex::new(vec![]);
+///     let mut session = pin!(session(&log));
+///     let _ = tokio::time::timeout(Duration::from_millis(10), session.as_mut().shim()).await;
+///
+///     let deadline = future(tokio::time::sleep(Duration::from_secs(1)));
+///     session.cancel(deadline).shim().await.unwrap();
+///     assert_eq!(*log.lock().unwrap(), vec!["hello", "goodbye"]);
+/// }
+/// ```
+pub trait Cancellable: Effective<Async = Async> {
+    /// Polls the cleanup of the effect, cancelling it on the first call.
+    /// Items and failures produced during the cleanup are discarded.
+    fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()>;
+
+    /// Cancels the effect, resolving once its cleanup has completed,
+    /// or with [`DeadlineElapsed`] if `deadline` resolves first
+    fn cancel<D>(self: Pin<&mut Self>, deadline: D) -> Cancel<'_, Self, D>
+    where
+        D: Effective<Produces = Single, Async = Async>,
+    {
+        Cancel {
+            effect: self,
+            deadline,
+        }
+    }
+}
+
+/// Returned from a `#[effect(cancellable)]` await when the effect is cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the effect was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The cleanup of a cancelled effect did not complete before its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineElapsed;
+
+impl fmt::Display for DeadlineElapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the cleanup deadline elapsed")
+    }
+}
+
+impl std::error::Error for DeadlineElapsed {}
+
+pin_project_lite::pin_project!(
+    /// Effect returned by [`Cancellable::cancel`]
+    #[must_use = "effects do nothing unless awaited"]
+    pub struct Cancel<'a, E: ?Sized, D> {
+        effect: Pin<&'a mut E>,
+        #[pin]
+        deadline: D,
+    }
+);
+
+impl<E, D> Effective for Cancel<'_, E, D>
+where
+    E: Cancellable + ?Sized,
+    D: Effective<Produces = Single, Async = Async>,
+{
+    type Item = Result<(), DeadlineElapsed>;
+    type Failure = std::convert::Infallible;
+    type Produces = Single;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        if this.effect.as_mut().poll_cancel(cx).is_ready() {
+            return EffectResult::Item(Ok(()));
+        }
+        match this.deadline.poll_effect(cx) {
+            EffectResult::Pending(_) => EffectResult::Pending(Async),
+            _ => EffectResult::Item(Err(DeadlineElapsed)),
+        }
+    }
+}
+
+/// What a `#[effect(cancellable)]` generator suspends with
+#[doc(hidden)]
+pub enum CancelSuspend<Y> {
+    Ready(Y),
+    Pending,
+    /// The generator has finished its cleanup and must not be resumed
+    Cancelled,
+}
+
+/// Resume argument of `#[effect(cancellable)]` generators
+#[doc(hidden)]
+pub struct CancelContext {
+    cx: UnsafeContextRef,
+    cancelled: bool,
+}
+
+impl CancelContext {
+    #[doc(hidden)]
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    #[doc(hidden)]
+    pub fn into_context(self) -> UnsafeContextRef {
+        self.cx
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    Running,
+    Cancelling,
+    Done,
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct CancellableImpl<G, M> {
+        #[pin]
+        generator: G,
+        state: State,
+        _mode: std::marker::PhantomData<fn() -> M>,
+    }
+);
+
+impl<G, M> CancellableImpl<G, M> {
+    #[doc(hidden)]
+    pub fn create<Y, R>(
+        generator: G,
+    ) -> impl Cancellable<Item = M::Item, Failure = M::Failure, Produces = M::Produces>
+    where
+        G: Generator<CancelContext, Yield = CancelSuspend<Y>, Return = R>,
+        M: Mode<Y, R>,
+    {
+        Self {
+            generator,
+            state: State::Start,
+            _mode: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<G, M, Y, R> Effective for CancellableImpl<G, M>
+where
+    G: Generator<CancelContext, Yield = CancelSuspend<Y>, Return = R>,
+    M: Mode<Y, R>,
+{
+    type Item = M::Item;
+    type Failure = M::Failure;
+    type Produces = M::Produces;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        match *this.state {
+            State::Start => *this.state = State::Running,
+            State::Running => {}
+            State::Cancelling | State::Done => {
+                panic!("effect polled after it completed or was cancelled")
+            }
+        }
+        let resume = CancelContext {
+            cx: cx.into(),
+            cancelled: false,
+        };
+        match this.generator.resume(resume) {
+            GeneratorState::Yielded(CancelSuspend::Ready(y)) => EffectResult::Item(M::item(y)),
+            GeneratorState::Yielded(CancelSuspend::Pending) => EffectResult::Pending(Async),
+            GeneratorState::Yielded(CancelSuspend::Cancelled) => unreachable!(),
+            GeneratorState::Complete(r) => {
+                *this.state = State::Done;
+                M::complete(r)
+            }
+        }
+    }
+}
+
+impl<G, M, Y, R> Cancellable for CancellableImpl<G, M>
+where
+    G: Generator<CancelContext, Yield = CancelSuspend<Y>, Return = R>,
+    M: Mode<Y, R>,
+{
+    fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut this = self.project();
+        let mut cancelled = match *this.state {
+            // the body never ran, so there is nothing to clean up
+            State::Start | State::Done => {
+                *this.state = State::Done;
+                return Poll::Ready(());
+            }
+            State::Running => {
+                *this.state = State::Cancelling;
+                true
+            }
+            State::Cancelling => false,
+        };
+        loop {
+            let resume = CancelContext {
+                cx: (&mut *cx).into(),
+                cancelled: std::mem::take(&mut cancelled),
+            };
+            match this.generator.as_mut().resume(resume) {
+                GeneratorState::Yielded(CancelSuspend::Ready(_)) => {}
+                GeneratorState::Yielded(CancelSuspend::Pending) => return Poll::Pending,
+                GeneratorState::Yielded(CancelSuspend::Cancelled) | GeneratorState::Complete(_) => {
+                    *this.state = State::Done;
+                    return Poll::Ready(());
+                }
+            }
+        }
+    }
+}
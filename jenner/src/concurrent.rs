@@ -0,0 +1,225 @@
+use std::{collections::VecDeque, convert::Infallible, ops::ControlFlow, pin::Pin, task::Context};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Failure, Multiple};
+
+use crate::EffectiveReturn;
+
+/// Moves a for-loop item into the generator of a concurrent body.
+///
+/// A generator only captures a `Copy` item by value if it is used through a method like this.
+#[doc(hidden)]
+pub struct Owned<T>(pub T);
+
+impl<T> Owned<T> {
+    #[doc(hidden)]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// The failure of a concurrent for-loop, from either the stream or one of the bodies
+#[doc(hidden)]
+pub trait MergeFailure<F> {
+    type Output;
+    fn stream(self) -> Self::Output;
+    fn body(failure: F) -> Self::Output;
+}
+
+impl MergeFailure<Infallible> for Infallible {
+    type Output = Infallible;
+    fn stream(self) -> Infallible {
+        match self {}
+    }
+    fn body(failure: Infallible) -> Infallible {
+        match failure {}
+    }
+}
+
+impl<E> MergeFailure<Failure<E>> for Infallible {
+    type Output = Failure<E>;
+    fn stream(self) -> Failure<E> {
+        match self {}
+    }
+    fn body(failure: Failure<E>) -> Failure<E> {
+        failure
+    }
+}
+
+impl<E> MergeFailure<Infallible> for Failure<E> {
+    type Output = Failure<E>;
+    fn stream(self) -> Failure<E> {
+        self
+    }
+    fn body(failure: Infallible) -> Failure<E> {
+        match failure {}
+    }
+}
+
+impl<E> MergeFailure<Failure<E>> for Failure<E> {
+    type Output = Failure<E>;
+    fn stream(self) -> Failure<E> {
+        self
+    }
+    fn body(failure: Failure<E>) -> Failure<E> {
+        failure
+    }
+}
+
+struct Running<B: Effective> {
+    body: Pin<Box<B>>,
+    /// Items the body yielded that have not been passed on yet
+    yielded: VecDeque<B::Item>,
+    done: bool,
+}
+
+pin_project_lite::pin_project!(
+    /// Runs the bodies of a `#[effect(async, concurrent(n))]` for-loop,
+    /// producing the items they yield
+    #[doc(hidden)]
+    pub struct ConcurrentFor<S, F, B: Effective> {
+        #[pin]
+        stream: S,
+        stream_done: bool,
+        body: F,
+        limit: usize,
+        ordered: bool,
+        running: VecDeque<Running<B>>,
+        broken: bool,
+    }
+);
+
+impl<S, F, B> ConcurrentFor<S, F, B>
+where
+    S: Effective<Produces = Multiple>,
+    F: FnMut(S::Item) -> B,
+    B: EffectiveReturn<Output = ControlFlow<()>, Produces = Multiple>,
+    S::Failure: MergeFailure<B::Failure>,
+{
+    #[doc(hidden)]
+    pub fn new(stream: S, limit: usize, ordered: bool, body: F) -> Self {
+        assert!(
+            limit > 0,
+            "concurrent for-loops must run at least one body at once"
+        );
+        Self {
+            stream,
+            stream_done: false,
+            body,
+            limit,
+            ordered,
+            running: VecDeque::new(),
+            broken: false,
+        }
+    }
+}
+
+impl<S, F, B> Effective for ConcurrentFor<S, F, B>
+where
+    S: Effective<Produces = Multiple>,
+    F: FnMut(S::Item) -> B,
+    B: EffectiveReturn<Output = ControlFlow<()>, Produces = Multiple>,
+    S::Failure: MergeFailure<B::Failure>,
+{
+    type Item = B::Item;
+    type Failure = <S::Failure as MergeFailure<B::Failure>>::Output;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let mut this = self.project();
+        loop {
+            // pass on what the bodies have yielded, in order of their items if ordered
+            if *this.ordered {
+                while let Some(front) = this.running.front_mut() {
+                    if let Some(item) = front.yielded.pop_front() {
+                        return EffectResult::Item(item);
+                    }
+                    if !front.done {
+                        break;
+                    }
+                    this.running.pop_front();
+                }
+            } else {
+                for running in this.running.iter_mut() {
+                    if let Some(item) = running.yielded.pop_front() {
+                        return EffectResult::Item(item);
+                    }
+                }
+                this.running.retain(|running| !running.done);
+            }
+
+            // the items yielded before a `break` have been passed on
+            if *this.broken {
+                this.running.clear();
+                return EffectResult::Done(Multiple);
+            }
+
+            let mut progress = false;
+            while !*this.stream_done && this.running.len() < *this.limit {
+                match this.stream.as_mut().poll_effect(cx) {
+                    EffectResult::Item(item) => {
+                        this.running.push_back(Running {
+                            body: Box::pin((this.body)(item)),
+                            yielded: VecDeque::new(),
+                            done: false,
+                        });
+                    }
+                    EffectResult::Done(_) => *this.stream_done = true,
+                    EffectResult::Failure(failure) => {
+                        this.running.clear();
+                        return EffectResult::Failure(failure.stream());
+                    }
+                    EffectResult::Pending(_) => break,
+                }
+            }
+            if *this.stream_done && this.running.is_empty() {
+                return EffectResult::Done(Multiple);
+            }
+
+            let mut failed = None;
+            let mut broke = None;
+            for (i, running) in this.running.iter_mut().enumerate() {
+                if running.done {
+                    continue;
+                }
+                match running.body.as_mut().poll_effect(cx) {
+                    EffectResult::Item(item) => running.yielded.push_back(item),
+                    EffectResult::Done(_) => {
+                        running.done = true;
+                        if let Some(ControlFlow::Break(())) = running.body.as_mut().take_output() {
+                            broke = Some(i);
+                            break;
+                        }
+                    }
+                    EffectResult::Failure(failure) => {
+                        failed = Some(failure);
+                        break;
+                    }
+                    EffectResult::Pending(_) => continue,
+                }
+                progress = true;
+            }
+            // a failure cancels the bodies still in flight
+            if let Some(failure) = failed {
+                this.running.clear();
+                return EffectResult::Failure(S::Failure::body(failure));
+            }
+            // a `break` cancels the bodies still in flight, but the items they have already
+            // yielded are passed on first. When ordered, that is only the bodies up to the one
+            // that broke, as the later items come after the `break`
+            if let Some(i) = broke {
+                *this.broken = true;
+                if *this.ordered {
+                    this.running.truncate(i + 1);
+                }
+                for running in this.running.iter_mut() {
+                    running.done = true;
+                }
+                continue;
+            }
+            if !progress {
+                return EffectResult::Pending(Async);
+            }
+        }
+    }
+}
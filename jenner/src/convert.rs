@@ -0,0 +1,265 @@
+use std::{
+    async_iter::AsyncIterator,
+    convert::Infallible,
+    future::{Future, IntoFuture},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use effective::{Async, Blocking, EffectResult, Effective, EffectiveResult, Multiple, Single};
+use futures_core::Stream;
+
+use crate::lift::{Delegate, Delegation};
+
+/// Wrapper used to pick an effect conversion at `.await` sites.
+///
+/// Uses auto-ref specialisation: `(&mut Await::new(x)).into_effect()` resolves to
+/// [`AwaitEffective`] if `x` is already an [`Effective`], falling back to [`AwaitFuture`]
+/// for anything that implements [`IntoFuture`].
+#[doc(hidden)]
+pub struct Await<T>(Option<T>);
+
+impl<T> Await<T> {
+    #[doc(hidden)]
+    pub fn new(value: T) -> Self {
+        Self(Some(value))
+    }
+
+    fn take(&mut self) -> T {
+        self.0
+            .take()
+            .expect("await conversion should only be performed once")
+    }
+}
+
+#[doc(hidden)]
+pub trait AwaitEffective {
+    type Effect;
+    fn into_effect(&mut self) -> Self::Effect;
+}
+
+impl<E> AwaitEffective for Await<E>
+where
+    E: Effective<Produces = Single>,
+{
+    type Effect = E;
+    fn into_effect(&mut self) -> Self::Effect {
+        self.take()
+    }
+}
+
+#[doc(hidden)]
+pub trait AwaitFuture {
+    type Effect;
+    fn into_effect(&mut self) -> Self::Effect;
+}
+
+impl<F> AwaitFuture for &mut Await<F>
+where
+    F: IntoFuture,
+{
+    type Effect = FutureEffect<F::IntoFuture>;
+    fn into_effect(&mut self) -> Self::Effect {
+        FutureEffect {
+            future: self.take().into_future(),
+        }
+    }
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct FutureEffect<F> {
+        #[pin]
+        future: F,
+    }
+);
+
+impl<F> FutureEffect<F> {
+    #[doc(hidden)]
+    pub fn new(future: F) -> Self {
+        Self { future }
+    }
+}
+
+impl<F> Effective for FutureEffect<F>
+where
+    F: Future,
+{
+    type Item = F::Output;
+    type Failure = Infallible;
+    type Produces = Single;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.project().future.poll(cx) {
+            Poll::Ready(x) => EffectResult::Item(x),
+            Poll::Pending => EffectResult::Pending(Async),
+        }
+    }
+}
+
+/// Wrapper used to pick an effect conversion for `#[effect(async)]` for-loops.
+///
+/// The call site starts from `&mut &mut &mut &mut IntoEffective` and each auto-deref is tried in turn,
+/// so the conversions are preferred in order [`Effective`], [`Stream`], [`AsyncIterator`] and finally [`IntoIterator`].
+#[doc(hidden)]
+pub struct IntoEffective<T>(Option<T>);
+
+impl<T> IntoEffective<T> {
+    #[doc(hidden)]
+    pub fn new(value: T) -> Self {
+        Self(Some(value))
+    }
+
+    fn take(&mut self) -> T {
+        self.0
+            .take()
+            .expect("for-loop conversion should only be performed once")
+    }
+}
+
+#[doc(hidden)]
+pub trait IntoEffectiveEffect {
+    type Effect;
+    fn into_effective(&mut self) -> Self::Effect;
+}
+
+impl<E> IntoEffectiveEffect for &mut &mut &mut IntoEffective<E>
+where
+    E: Effective,
+    E::Produces: Delegation,
+{
+    type Effect = Delegate<E>;
+    fn into_effective(&mut self) -> Self::Effect {
+        Delegate::new(self.take())
+    }
+}
+
+#[doc(hidden)]
+pub trait IntoEffectiveStream {
+    type Effect;
+    fn into_effective(&mut self) -> Self::Effect;
+}
+
+impl<S> IntoEffectiveStream for &mut &mut IntoEffective<S>
+where
+    S: Stream,
+{
+    type Effect = StreamEffect<S>;
+    fn into_effective(&mut self) -> Self::Effect {
+        StreamEffect {
+            stream: self.take(),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub trait IntoEffectiveAsyncIter {
+    type Effect;
+    fn into_effective(&mut self) -> Self::Effect;
+}
+
+impl<I> IntoEffectiveAsyncIter for &mut IntoEffective<I>
+where
+    I: AsyncIterator,
+{
+    type Effect = AsyncIterEffect<I>;
+    fn into_effective(&mut self) -> Self::Effect {
+        AsyncIterEffect { iter: self.take() }
+    }
+}
+
+#[doc(hidden)]
+pub trait IntoEffectiveIter {
+    type Effect;
+    fn into_effective(&mut self) -> Self::Effect;
+}
+
+impl<I> IntoEffectiveIter for IntoEffective<I>
+where
+    I: IntoIterator,
+{
+    type Effect = IterEffect<I::IntoIter>;
+    fn into_effective(&mut self) -> Self::Effect {
+        IterEffect {
+            iter: self.take().into_iter(),
+        }
+    }
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct StreamEffect<S> {
+        #[pin]
+        stream: S,
+    }
+);
+
+impl<S> Effective for StreamEffect<S>
+where
+    S: Stream,
+{
+    type Item = S::Item;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.project().stream.poll_next(cx) {
+            Poll::Ready(Some(x)) => EffectResult::Item(x),
+            Poll::Ready(None) => EffectResult::Done(Multiple),
+            Poll::Pending => EffectResult::Pending(Async),
+        }
+    }
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct AsyncIterEffect<I> {
+        #[pin]
+        iter: I,
+    }
+);
+
+impl<I> Effective for AsyncIterEffect<I>
+where
+    I: AsyncIterator,
+{
+    type Item = I::Item;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.project().iter.poll_next(cx) {
+            Poll::Ready(Some(x)) => EffectResult::Item(x),
+            Poll::Ready(None) => EffectResult::Done(Multiple),
+            Poll::Pending => EffectResult::Pending(Async),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct IterEffect<I> {
+    iter: I,
+}
+
+// `iter` is never pinned
+impl<I> Unpin for IterEffect<I> {}
+
+impl<I> Effective for IterEffect<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    type Async = Blocking;
+
+    fn poll_effect(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.get_mut().iter.next() {
+            Some(x) => EffectResult::Item(x),
+            None => EffectResult::Done(Multiple),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Sync generators as cooperative tasks, run by a deterministic [`Scheduler`].
+//!
+//! A task is a sync `#[effect(yields)]` generator that yields a [`Request`] whenever it gives up control.
+//! Time is measured in ticks rather than wall-clock time. On every tick, the scheduler resumes each task
+//! that is ready, in the order they were spawned, so runs are reproducible.
+//!
+//! ```
+//! #![feature(generators)]
+//!
+//! use jenner::{
+//!     coop::{Request, Scheduler, Signal},
+//!     effect,
+//! };
+//! use std::cell::RefCell;
+//!
+//! #[effect(yields)]
+//! fn blink(name: &'static str, every: u64, log: &RefCell<Vec<String>>) -> Request {
+//!     for _ in 0..3 {
+//!         yield Request::Sleep(every);
+//!         log.borrow_mut().push(format!("{name} blinks"));
+//!     }
+//! }
+//!
+//! #[effect(yields)]
+//! fn door(opened: &Signal, log: &RefCell<Vec<String>>) -> Request {
+//!     yield Request::WaitFor(opened.clone());
+//!     log.borrow_mut().push("door opens".to_owned());
+//! }
+//!
+//! #[effect(yields)]
+//! fn key(opened: &Signal) -> Request {
+//!     yield Request::Sleep(4);
+//!     opened.fire();
+//! }
+//!
+//! let log = RefCell::new(vec![]);
+//! let opened = Signal::new();
+//! let mut scheduler = Scheduler::new();
+//! scheduler.spawn(blink("fast", 2, &log));
+//! scheduler.spawn(blink("slow", 3, &log));
+//! scheduler.spawn(door(&opened, &log));
+//! scheduler.spawn(key(&opened));
+//! scheduler.run();
+//!
+//! assert_eq!(scheduler.now(), 10);
+//! assert_eq!(
+//!     *log.borrow(),
+//!     [
+//!         "fast blinks",
+//!         "slow blinks",
+//!         "fast blinks",
+//!         "door opens",
+//!         "fast blinks",
+//!         "slow blinks",
+//!         "slow blinks",
+//!     ]
+//! );
+//! ```
+
+use std::{cell::Cell, convert::Infallible, pin::Pin, rc::Rc, task::Context};
+
+use effective::{Blocking, EffectResult, Effective, Multiple};
+
+use crate::{lift::blocking, noop::noop_waker};
+
+/// What a task waits for before the [`Scheduler`] resumes it
+#[derive(Debug, Clone)]
+pub enum Request {
+    /// Resume the task this many ticks later. `Sleep(0)` and `Sleep(1)` both resume it on the next tick.
+    Sleep(u64),
+    /// Resume the task on the tick after the signal is fired
+    WaitFor(Signal),
+}
+
+/// A signal that tasks can wait for with [`Request::WaitFor`].
+///
+/// Firing the signal resumes the tasks that are waiting for it at the time.
+/// Tasks that start waiting afterwards wait for the next time it is fired.
+#[derive(Debug, Clone, Default)]
+pub struct Signal(Rc<Cell<u64>>);
+
+impl Signal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resumes the tasks waiting for this signal on the next tick
+    pub fn fire(&self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+type Task<'a> = Pin<
+    Box<
+        dyn Effective<Item = Request, Failure = Infallible, Produces = Multiple, Async = Blocking>
+            + 'a,
+    >,
+>;
+
+enum Wait {
+    /// Ready from this tick on
+    Until(u64),
+    /// Ready once the signal has been fired more than this many times
+    Signal(Signal, u64),
+}
+
+/// Runs sync generators as cooperative tasks, in ticks. See [the module docs](self) for an example.
+#[derive(Default)]
+pub struct Scheduler<'a> {
+    now: u64,
+    tasks: Vec<(Task<'a>, Wait)>,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a task, which is first resumed on the current tick
+    pub fn spawn<E>(&mut self, task: E)
+    where
+        E: Effective<Item = Request, Failure = Infallible, Produces = Multiple, Async = Blocking>
+            + 'a,
+    {
+        self.tasks.push((Box::pin(task), Wait::Until(self.now)));
+    }
+
+    /// The current tick
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// How many tasks have not completed yet
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Resumes every task that is ready, in the order they were spawned, then advances to the next tick.
+    /// Returns whether any task was resumed.
+    pub fn tick(&mut self) -> bool {
+        let now = self.now;
+        let ready: Vec<bool> = self.tasks.iter().map(|(_, wait)| wait.ready(now)).collect();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut ready = ready.into_iter();
+        let mut resumed = false;
+        self.tasks.retain_mut(|(task, wait)| {
+            if !ready.next().unwrap_or(false) {
+                return true;
+            }
+            resumed = true;
+            match task.as_mut().poll_effect(&mut cx) {
+                EffectResult::Item(Request::Sleep(ticks)) => {
+                    *wait = Wait::Until(now.saturating_add(ticks.max(1)));
+                    true
+                }
+                EffectResult::Item(Request::WaitFor(signal)) => {
+                    let fired = signal.0.get();
+                    *wait = Wait::Signal(signal, fired);
+                    true
+                }
+                EffectResult::Done(_) => false,
+                EffectResult::Failure(x) => match x {},
+                EffectResult::Pending(x) => blocking(x),
+            }
+        });
+        self.now = self.now.saturating_add(1);
+        resumed
+    }
+
+    /// Runs ticks until every task has completed, or the remaining tasks are all waiting for signals.
+    /// Ticks where every task is asleep are skipped.
+    pub fn run(&mut self) {
+        self.run_until(u64::MAX);
+    }
+
+    /// Runs ticks like [`run`](Self::run), stopping at the given tick
+    pub fn run_until(&mut self, end: u64) {
+        while self.now < end {
+            let now = self.now;
+            if self.tasks.iter().any(|(_, wait)| wait.ready(now)) {
+                self.tick();
+                continue;
+            }
+            let next = self.tasks.iter().filter_map(|(_, wait)| match wait {
+                Wait::Until(tick) => Some(*tick),
+                Wait::Signal(..) => None,
+            });
+            match next.min() {
+                Some(next) => self.now = next.min(end),
+                // only a task could fire the signals the rest are waiting for
+                None => break,
+            }
+        }
+    }
+}
+
+impl Wait {
+    fn ready(&self, now: u64) -> bool {
+        match self {
+            Wait::Until(tick) => *tick <= now,
+            Wait::Signal(signal, fired) => signal.0.get() > *fired,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Wake, Waker},
+};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Failure, Multiple};
+
+/// How many items an output can buffer while another output is being read ahead of it.
+///
+/// Once an output has this many items buffered, the other outputs wait for it to be drained.
+/// An output that is never polled must be dropped, or its siblings can wait forever.
+pub const DEMUX_CAPACITY: usize = 16;
+
+/// One output of a multi-output generator, created with `#[effect(yields(name: Type, ...))]`
+/// and fed with `yield name(value)`.
+///
+/// All the outputs share one generator. Polling an output resumes the generator until it yields
+/// an item for that output, buffering the items for the other outputs along the way.
+/// Poll the outputs concurrently, or drop the ones that are not needed,
+/// as reading ahead waits once another output has [`DEMUX_CAPACITY`] items buffered.
+///
+/// If the generator fails, every output reports the failure after its buffered items,
+/// which requires a failure that can be cloned. Dropping an output discards any items routed to it.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::{Async, Effective, EffectiveExt, Multiple};
+/// use jenner::effect;
+/// use std::convert::Infallible;
+///
+/// #[effect(yields(evens: u32, odds: u32))]
+/// async fn split(n: u32) {
+///     for i in 0..n {
+///         if i % 2 == 0 {
+///             yield evens(i);
+///         } else {
+///             yield odds(i);
+///         }
+///     }
+/// }
+///
+/// #[effect]
+/// async fn collect(
+///     input: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+/// ) -> Vec<u32> {
+///     let mut v = vec![];
+///     #[effect(async)]
+///     for i in input {
+///         v.push(i);
+///     }
+///     v
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let SplitOutputs { evens, odds } = split(6);
+///     assert_eq!(collect(odds).shim().await, vec![1, 3, 5]);
+///     assert_eq!(collect(evens).shim().await, vec![0, 2, 4]);
+/// }
+/// ```
+pub struct DemuxOutput<E: Effective, T> {
+    shared: Arc<Demux<E>>,
+    index: usize,
+    extract: fn(E::Item) -> T,
+}
+
+/// The state shared between the outputs of a multi-output generator
+#[doc(hidden)]
+pub struct Demux<E: Effective> {
+    state: Mutex<DemuxState<E>>,
+    wakers: Arc<Fanout>,
+}
+
+struct DemuxState<E: Effective> {
+    source: Pin<Box<E>>,
+    route: fn(&E::Item) -> usize,
+    capacity: usize,
+    queues: Vec<VecDeque<E::Item>>,
+    closed: Vec<bool>,
+    done: bool,
+    failure: Option<E::Failure>,
+    /// The outputs that have not yet reported the failure
+    unreported: Vec<bool>,
+}
+
+/// A failure that can be delivered to more than one consumer
+#[doc(hidden)]
+pub trait SharedFailure {
+    fn duplicate(&self) -> Self;
+}
+
+impl SharedFailure for Infallible {
+    fn duplicate(&self) -> Self {
+        match *self {}
+    }
+}
+
+impl<E: Clone> SharedFailure for Failure<E> {
+    fn duplicate(&self) -> Self {
+        Failure(self.0.clone())
+    }
+}
+
+/// Wakes every pending output when the shared generator is ready to make progress.
+/// Also used by the consumers of [`share`](crate::share).
+pub struct Fanout(pub Mutex<Vec<Option<Waker>>>);
+
+impl Wake for Fanout {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers: Vec<_> = lock(&self.0).iter_mut().filter_map(Option::take).collect();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<E: Effective> Demux<E> {
+    #[doc(hidden)]
+    pub fn new(source: E, route: fn(&E::Item) -> usize, outputs: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(DemuxState {
+                source: Box::pin(source),
+                route,
+                capacity: DEMUX_CAPACITY,
+                queues: (0..outputs).map(|_| VecDeque::new()).collect(),
+                closed: vec![false; outputs],
+                done: false,
+                failure: None,
+                unreported: vec![false; outputs],
+            }),
+            wakers: Arc::new(Fanout(Mutex::new(vec![None; outputs]))),
+        })
+    }
+}
+
+impl<E: Effective, T> DemuxOutput<E, T> {
+    #[doc(hidden)]
+    pub fn new(shared: &Arc<Demux<E>>, index: usize, extract: fn(E::Item) -> T) -> Self {
+        Self {
+            shared: shared.clone(),
+            index,
+            extract,
+        }
+    }
+}
+
+impl<E, T> Effective for DemuxOutput<E, T>
+where
+    E: Effective<Produces = Multiple, Async = Async>,
+    E::Failure: SharedFailure,
+{
+    type Item = T;
+    type Failure = E::Failure;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let index = self.index;
+        let mut state = lock(&self.shared.state);
+
+        if let Some(item) = state.queues[index].pop_front() {
+            if state.queues[index].len() + 1 == state.capacity {
+                // this output was holding the others back
+                self.shared.wakers.wake_by_ref();
+            }
+            return EffectResult::Item((self.extract)(item));
+        }
+
+        loop {
+            if state.done {
+                let unreported = std::mem::take(&mut state.unreported[index]);
+                return match &state.failure {
+                    Some(failure) if unreported => EffectResult::Failure(failure.duplicate()),
+                    _ => EffectResult::Done(Multiple),
+                };
+            }
+
+            lock(&self.shared.wakers.0)[index] = Some(cx.waker().clone());
+            let state = &mut *state;
+            if state
+                .queues
+                .iter()
+                .any(|queue| queue.len() >= state.capacity)
+            {
+                return EffectResult::Pending(Async);
+            }
+
+            let waker = Waker::from(self.shared.wakers.clone());
+            match state
+                .source
+                .as_mut()
+                .poll_effect(&mut Context::from_waker(&waker))
+            {
+                EffectResult::Item(item) => {
+                    let route = (state.route)(&item);
+                    if route == index {
+                        lock(&self.shared.wakers.0)[index] = None;
+                        return EffectResult::Item((self.extract)(item));
+                    }
+                    if !state.closed[route] {
+                        state.queues[route].push_back(item);
+                        if let Some(waker) = lock(&self.shared.wakers.0)[route].take() {
+                            waker.wake();
+                        }
+                    }
+                }
+                EffectResult::Failure(failure) => {
+                    state.done = true;
+                    state.failure = Some(failure);
+                    state.unreported.fill(true);
+                    self.shared.wakers.wake_by_ref();
+                }
+                EffectResult::Done(_) => {
+                    state.done = true;
+                    self.shared.wakers.wake_by_ref();
+                }
+                EffectResult::Pending(_) => return EffectResult::Pending(Async),
+            }
+        }
+    }
+}
+
+impl<E: Effective, T> Drop for DemuxOutput<E, T> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared.state);
+        state.closed[self.index] = true;
+        state.queues[self.index].clear();
+        lock(&self.shared.wakers.0)[self.index] = None;
+        drop(state);
+        // the other outputs might have been waiting for this one to be drained
+        self.shared.wakers.wake_by_ref();
+    }
+}
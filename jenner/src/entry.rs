@@ -0,0 +1,75 @@
+use std::{
+    convert::Infallible,
+    fmt,
+    pin::pin,
+    process,
+    time::{Duration, Instant},
+};
+
+use effective::{EffectResult, Effective, Failure, Single};
+
+use crate::block::park_until;
+
+/// The failures that `#[jenner::test]` and `#[jenner::main]` can report
+#[doc(hidden)]
+pub trait EntryFailure {
+    fn report(self) -> String;
+}
+
+impl EntryFailure for Infallible {
+    fn report(self) -> String {
+        match self {}
+    }
+}
+
+impl<E: fmt::Debug> EntryFailure for Failure<E> {
+    fn report(self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// Runs the body of a `#[jenner::test]`, panicking if it fails or times out
+#[doc(hidden)]
+pub fn run_test<E>(effect: E, timeout: Option<Duration>) -> E::Item
+where
+    E: Effective<Produces = Single>,
+    E::Failure: EntryFailure,
+{
+    match run(effect, timeout) {
+        Ok(x) => x,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Runs the body of a `#[jenner::main]`, exiting with an error code if it fails or times out
+#[doc(hidden)]
+pub fn run_main<E>(effect: E, timeout: Option<Duration>) -> E::Item
+where
+    E: Effective<Produces = Single>,
+    E::Failure: EntryFailure,
+{
+    match run(effect, timeout) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1)
+        }
+    }
+}
+
+fn run<E>(effect: E, timeout: Option<Duration>) -> Result<E::Item, String>
+where
+    E: Effective<Produces = Single>,
+    E::Failure: EntryFailure,
+{
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    match park_until(pin!(effect), deadline) {
+        Some(EffectResult::Item(x)) => Ok(x),
+        Some(EffectResult::Failure(x)) => Err(format!("the effect failed: {}", x.report())),
+        Some(EffectResult::Done(_) | EffectResult::Pending(_)) => unreachable!(),
+        None => Err(format!(
+            "the effect timed out after {:?}",
+            timeout.unwrap_or_default()
+        )),
+    }
+}
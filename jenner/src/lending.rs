@@ -0,0 +1,311 @@
+use std::{
+    convert::Infallible,
+    ops::{Generator, GeneratorState},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use effective::{Async, Blocking, EffectResult, Failure, Multiple};
+
+use crate::asynch::UnsafeContextRef;
+
+/// A [`Multiple`] effect whose items borrow from the effect itself.
+///
+/// Created with `#[effect(yields, lending)]`, where the return type is a reference.
+/// Each item is only valid until the effect is next polled, so these effects can yield
+/// references into their own state. They can be iterated over using `#[effect(lending)]` for-loops.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+///
+/// /// Yields each line, reusing the same buffer
+/// #[effect(yields, lending)]
+/// fn lines(input: String) -> &str {
+///     let mut buf = String::new();
+///     for line in input.lines() {
+///         buf.clear();
+///         buf.push_str(line.trim());
+///         yield &buf;
+///     }
+/// }
+///
+/// #[effect]
+/// async fn lengths(input: String) -> Vec<usize> {
+///     let mut v = vec![];
+///     #[effect(lending)]
+///     for line in lines(input) {
+///         v.push(line.len());
+///     }
+///     v
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let v = lengths(" a \n bb\nccc ".to_owned()).shim().await;
+///     assert_eq!(v, vec![1, 2, 3]);
+/// }
+/// ```
+pub trait LendingEffective {
+    /// Pointer to the lent item, eg `*const [u8]` for items of `&'a [u8]`
+    type Lend: Lent;
+    type Failure;
+    type Async;
+
+    /// Polls for the next item, which borrows the effect until it is polled again
+    fn poll_lend<'a>(
+        self: Pin<&'a mut Self>,
+        cx: &mut Context<'_>,
+    ) -> EffectResult<LentItem<'a, Self>, Self::Failure, Multiple, Self::Async>
+    where
+        Self::Lend: 'a;
+}
+
+/// The item lent by a [`LendingEffective`] for the lifetime `'a`
+pub type LentItem<'a, E> = <<E as LendingEffective>::Lend as Lent>::Item<'a>;
+
+/// A pointer that can be turned back into a borrow. Implemented for `*const T` and `*mut T`
+pub trait Lent {
+    /// The borrowed form of this pointer
+    type Item<'a>
+    where
+        Self: 'a;
+
+    /// # Safety
+    /// The pointer must be valid for reads (and writes, for `*mut T`) for the lifetime `'a`
+    unsafe fn lend<'a>(self) -> Self::Item<'a>
+    where
+        Self: 'a;
+}
+
+impl<T: ?Sized> Lent for *const T {
+    type Item<'a>
+        = &'a T
+    where
+        Self: 'a;
+
+    unsafe fn lend<'a>(self) -> &'a T
+    where
+        Self: 'a,
+    {
+        &*self
+    }
+}
+
+impl<T: ?Sized> Lent for *mut T {
+    type Item<'a>
+        = &'a mut T
+    where
+        Self: 'a;
+
+    unsafe fn lend<'a>(self) -> &'a mut T
+    where
+        Self: 'a,
+    {
+        &mut *self
+    }
+}
+
+/// Erases the lifetime of a yielded reference so it can leave the generator
+#[doc(hidden)]
+pub trait IntoLent {
+    type Lent: Lent;
+    fn into_lent(self) -> Self::Lent;
+}
+
+impl<T: ?Sized> IntoLent for &T {
+    type Lent = *const T;
+    fn into_lent(self) -> *const T {
+        self
+    }
+}
+
+impl<T: ?Sized> IntoLent for &mut T {
+    type Lent = *mut T;
+    fn into_lent(self) -> *mut T {
+        self
+    }
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct SyncLendingGeneratorImpl<G> {
+        #[pin]
+        generator: G,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct SyncFallibleLendingGeneratorImpl<G> {
+        #[pin]
+        generator: G,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct AsyncLendingGeneratorImpl<G> {
+        #[pin]
+        generator: G,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct AsyncFallibleLendingGeneratorImpl<G> {
+        #[pin]
+        generator: G,
+    }
+);
+
+impl<G> SyncLendingGeneratorImpl<G> {
+    #[doc(hidden)]
+    pub fn create<L: Lent>(
+        generator: G,
+    ) -> impl LendingEffective<Lend = L, Failure = Infallible, Async = Blocking>
+    where
+        G: Generator<(), Yield = L, Return = ()>,
+    {
+        Self { generator }
+    }
+}
+
+impl<G> SyncFallibleLendingGeneratorImpl<G> {
+    #[doc(hidden)]
+    pub fn create<L: Lent, E>(
+        generator: G,
+    ) -> impl LendingEffective<Lend = L, Failure = Failure<E>, Async = Blocking>
+    where
+        G: Generator<(), Yield = L, Return = Result<(), E>>,
+    {
+        Self { generator }
+    }
+}
+
+impl<G> AsyncLendingGeneratorImpl<G> {
+    #[doc(hidden)]
+    pub fn create<L: Lent>(
+        generator: G,
+    ) -> impl LendingEffective<Lend = L, Failure = Infallible, Async = Async>
+    where
+        G: Generator<UnsafeContextRef, Yield = Poll<L>, Return = ()>,
+    {
+        Self { generator }
+    }
+}
+
+impl<G> AsyncFallibleLendingGeneratorImpl<G> {
+    #[doc(hidden)]
+    pub fn create<L: Lent, E>(
+        generator: G,
+    ) -> impl LendingEffective<Lend = L, Failure = Failure<E>, Async = Async>
+    where
+        G: Generator<UnsafeContextRef, Yield = Poll<L>, Return = Result<(), E>>,
+    {
+        Self { generator }
+    }
+}
+
+// Safety for all of the below:
+// The generator is pinned, so the state that the yielded pointer refers to cannot move.
+// The lent item borrows `self`, so the generator cannot be resumed
+// (and the state modified or dropped) while the item is still in use.
+
+impl<G, L> LendingEffective for SyncLendingGeneratorImpl<G>
+where
+    G: Generator<(), Yield = L, Return = ()>,
+    L: Lent,
+{
+    type Lend = L;
+    type Failure = Infallible;
+    type Async = Blocking;
+
+    fn poll_lend<'a>(
+        self: Pin<&'a mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> EffectResult<LentItem<'a, Self>, Self::Failure, Multiple, Self::Async>
+    where
+        L: 'a,
+    {
+        match self.project().generator.resume(()) {
+            GeneratorState::Yielded(x) => EffectResult::Item(unsafe { x.lend() }),
+            GeneratorState::Complete(()) => EffectResult::Done(Multiple),
+        }
+    }
+}
+
+impl<G, L, E> LendingEffective for SyncFallibleLendingGeneratorImpl<G>
+where
+    G: Generator<(), Yield = L, Return = Result<(), E>>,
+    L: Lent,
+{
+    type Lend = L;
+    type Failure = Failure<E>;
+    type Async = Blocking;
+
+    fn poll_lend<'a>(
+        self: Pin<&'a mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> EffectResult<LentItem<'a, Self>, Self::Failure, Multiple, Self::Async>
+    where
+        L: 'a,
+    {
+        match self.project().generator.resume(()) {
+            GeneratorState::Yielded(x) => EffectResult::Item(unsafe { x.lend() }),
+            GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
+            GeneratorState::Complete(Ok(())) => EffectResult::Done(Multiple),
+        }
+    }
+}
+
+impl<G, L> LendingEffective for AsyncLendingGeneratorImpl<G>
+where
+    G: Generator<UnsafeContextRef, Yield = Poll<L>, Return = ()>,
+    L: Lent,
+{
+    type Lend = L;
+    type Failure = Infallible;
+    type Async = Async;
+
+    fn poll_lend<'a>(
+        self: Pin<&'a mut Self>,
+        cx: &mut Context<'_>,
+    ) -> EffectResult<LentItem<'a, Self>, Self::Failure, Multiple, Self::Async>
+    where
+        L: 'a,
+    {
+        match self.project().generator.resume(cx.into()) {
+            GeneratorState::Yielded(Poll::Ready(x)) => EffectResult::Item(unsafe { x.lend() }),
+            GeneratorState::Yielded(Poll::Pending) => EffectResult::Pending(Async),
+            GeneratorState::Complete(()) => EffectResult::Done(Multiple),
+        }
+    }
+}
+
+impl<G, L, E> LendingEffective for AsyncFallibleLendingGeneratorImpl<G>
+where
+    G: Generator<UnsafeContextRef, Yield = Poll<L>, Return = Result<(), E>>,
+    L: Lent,
+{
+    type Lend = L;
+    type Failure = Failure<E>;
+    type Async = Async;
+
+    fn poll_lend<'a>(
+        self: Pin<&'a mut Self>,
+        cx: &mut Context<'_>,
+    ) -> EffectResult<LentItem<'a, Self>, Self::Failure, Multiple, Self::Async>
+    where
+        L: 'a,
+    {
+        match self.project().generator.resume(cx.into()) {
+            GeneratorState::Yielded(Poll::Ready(x)) => EffectResult::Item(unsafe { x.lend() }),
+            GeneratorState::Yielded(Poll::Pending) => EffectResult::Pending(Async),
+            GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
+            GeneratorState::Complete(Ok(())) => EffectResult::Done(Multiple),
+        }
+    }
+}
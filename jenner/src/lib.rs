@@ -8,7 +8,6 @@
 //! #![feature(generators)]
 //!
 //! use effective::{Effective, EffectiveExt, Async, Multiple};
-//! use effective::wrappers::future;
 //! use jenner::effect;
 //! use std::{time::{Instant, Duration}, convert::Infallible};
 //!
@@ -18,7 +17,7 @@
 //! async fn countdown() -> u32 {
 //!     yield 5;
 //!     for i in (0..5).rev() {
-//!         future(tokio::time::sleep(Duration::from_millis(200))).await;
+//!         tokio::time::sleep(Duration::from_millis(200)).await;
 //!         yield i;
 //!     }
 //! }
@@ -82,7 +81,315 @@
 //!     assert_eq!(v, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
 //! }
 //! ```
-#![feature(generator_trait)]
+//!
+//! # Movable generators
+//!
+//! Generators are `static` by default, so the effects are `!Unpin` and must be pinned before use.
+//! `#[effect(movable)]` creates an [`Unpin`] effect instead, which can be used with iterator
+//! and stream adapters directly. Effects that are awaited across a yield are boxed.
+//!
+//! ```rust
+//! #![feature(generators)]
+//!
+//! use effective::EffectiveExt;
+//! use jenner::effect;
+//!
+//! #[effect(yields, movable)]
+//! fn naturals() -> u32 {
+//!     for i in 1.. {
+//!         yield i;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let v: Vec<_> = naturals().shim().take(3).collect();
+//!     assert_eq!(v, vec![1, 2, 3]);
+//! }
+//! ```
+//!
+//! The body cannot hold borrows of its own locals across a yield:
+//!
+//! ```compile_fail
+//! #![feature(generators)]
+//!
+//! #[jenner::effect(yields, movable)]
+//! fn chars() -> char {
+//!     let s = String::from("abc");
+//!     // error: borrow may still be in use when generator yields
+//!     for c in s.chars() {
+//!         yield c;
+//!     }
+//! }
+//! ```
+//!
+//! # Sync and async variants
+//!
+//! `#[effect(maybe_async)]` on an `async fn foo` also creates a blocking `foo_blocking` from the same body.
+//! Awaits and for-loops marked `#[effect(maybe_async)]` call the `_blocking` variant in the blocking function.
+//! Other awaits and `#[effect(async)]` for-loops park the thread while their effect is pending,
+//! like [`block_on`].
+//!
+//! Parking relies on the effect to wake the thread. Effects driven by a runtime,
+//! like tokio's timers and sockets, are only woken while that runtime is running,
+//! so `foo_blocking` deadlocks if it is called on the thread that drives them.
+//!
+//! ```rust
+//! #![feature(generators, stmt_expr_attributes)]
+//!
+//! use effective::EffectiveExt;
+//! use jenner::effect;
+//! use std::pin::pin;
+//!
+//! #[effect(yields, maybe_async)]
+//! async fn pages(n: u32) -> Vec<u32> {
+//!     for i in 0..n {
+//!         yield vec![i; 2];
+//!     }
+//! }
+//!
+//! #[effect(maybe_async)]
+//! async fn total(n: u32) -> u32 {
+//!     let mut sum = 0;
+//!     #[effect(maybe_async)]
+//!     for page in pages(n) {
+//!         sum += page.iter().sum::<u32>();
+//!     }
+//!     sum
+//! }
+//!
+//! #[effect(yields)]
+//! fn totals() -> u32 {
+//!     yield total_blocking(4).await;
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     assert_eq!(total(4).shim().await, 12);
+//!     assert_eq!(pin!(totals()).shim().next(), Some(12));
+//! }
+//! ```
+//!
+//! # Dispatch
+//!
+//! `#[effect(dispatch)]` functions choose an effect to return, rather than being a generator.
+//! Each branch can return a different effect type, as long as they all match the signature.
+//! Like awaits, the branches are lifted into the signature: infallible effects can be returned
+//! from fallible functions, blocking effects from async functions,
+//! and single effects from `yields` functions.
+//!
+//! ```rust
+//! #![feature(generators)]
+//!
+//! use effective::EffectiveExt;
+//! use jenner::effect;
+//! use std::pin::pin;
+//!
+//! #[effect(yields)]
+//! fn evens() -> u32 {
+//!     yield 0;
+//!     yield 2;
+//! }
+//!
+//! #[effect(yields)]
+//! fn odds() -> u32 {
+//!     yield 1;
+//!     yield 3;
+//! }
+//!
+//! #[effect(yields, dispatch)]
+//! fn parity(odd: bool) -> u32 {
+//!     if odd {
+//!         odds()
+//!     } else {
+//!         evens()
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let v: Vec<_> = pin!(parity(true)).shim().collect();
+//!     assert_eq!(v, vec![1, 3]);
+//! }
+//! ```
+//!
+//! # Cloning
+//!
+//! `#[effect(clone)]` creates a movable generator that is [`Clone`] when its state is,
+//! so iteration can be forked part way through.
+//!
+//! ```rust
+//! #![feature(generators, generator_clone)]
+//!
+//! use effective::EffectiveExt;
+//! use jenner::effect;
+//! use std::pin::{pin, Pin};
+//!
+//! #[effect(yields, clone)]
+//! fn squares() -> u32 {
+//!     for i in 0.. {
+//!         yield i * i;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let mut a = squares();
+//!     assert_eq!(Pin::new(&mut a).shim().nth(2), Some(4));
+//!     let b = a.clone();
+//!     assert_eq!(pin!(a).shim().next(), Some(9));
+//!     assert_eq!(pin!(b).shim().next(), Some(9));
+//! }
+//! ```
+//!
+//! # Cancellation
+//!
+//! `#[effect(cancellable)]` async functions can be [cancelled](Cancellable::cancel) while suspended.
+//! The pending `.await` runs the `jenner::on_cancel!` blocks in scope, which can await their own
+//! cleanup under a deadline set by the caller.
+//!
+//! # Effect handlers
+//!
+//! `#[effect(uses(Op, ...))]` bodies can `perform!` user-defined [`Operation`]s,
+//! suspending until the caller answers them. The caller provides the [`Handler`]s with [`handle`],
+//! so the same body can run against mocks in tests and real services in production.
+//! See [`Operation`] for an example.
+//!
+//! # Concurrent for-loops
+//!
+//! `#[effect(async, concurrent(n))]` for-loops run up to `n` bodies at once.
+//! Items yielded by the bodies are passed on in the order of the loop's items,
+//! or as soon as they are yielded with `#[effect(async, concurrent(n), unordered)]`.
+//! A `break` or a failure cancels the bodies still running. After a `break`, the items that were
+//! already yielded are still passed on, except for those of later items when ordered.
+//! The bodies can share the function's state by reference, but cannot `return`.
+//!
+//! ```
+//! #![feature(generators)]
+//!
+//! use effective::{Async, Effective, EffectiveExt, Multiple};
+//! use jenner::effect;
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! #[effect]
+//! async fn fetch(id: u32) -> String {
+//!     tokio::time::sleep(Duration::from_millis(100)).await;
+//!     format!("page {id}")
+//! }
+//!
+//! #[effect(yields)]
+//! async fn pages(ids: Vec<u32>) -> String {
+//!     #[effect(async, concurrent(4))]
+//!     for id in ids {
+//!         yield fetch(id).await;
+//!     }
+//! }
+//!
+//! #[effect]
+//! async fn collect(
+//!     input: impl Effective<Item = String, Failure = Infallible, Produces = Multiple, Async = Async>,
+//! ) -> Vec<String> {
+//!     let mut v = vec![];
+//!     #[effect(async)]
+//!     for page in input {
+//!         v.push(page);
+//!     }
+//!     v
+//! }
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let start = tokio::time::Instant::now();
+//!     let pages = collect(pages(vec![1, 2, 3, 4])).shim().await;
+//!     assert_eq!(pages, ["page 1", "page 2", "page 3", "page 4"]);
+//!     assert!(start.elapsed() < Duration::from_millis(200));
+//! }
+//! ```
+//!
+//! A concurrent for-loop is always async, so it cannot also be `lending` or `maybe_async`:
+//!
+//! ```compile_fail
+//! #![feature(generators)]
+//!
+//! #[jenner::effect(yields)]
+//! async fn ids(n: u32) -> u32 {
+//!     for i in 0..n {
+//!         yield i;
+//!     }
+//! }
+//!
+//! #[jenner::effect(yields)]
+//! async fn doubled(n: u32) -> u32 {
+//!     // error: `concurrent` for-loops cannot also be `lending` or `maybe_async`
+//!     #[effect(async, maybe_async, concurrent(2))]
+//!     for x in ids(n) {
+//!         yield x * 2;
+//!     }
+//! }
+//! ```
+//!
+//! and it needs a limit:
+//!
+//! ```compile_fail
+//! #![feature(generators)]
+//!
+//! #[jenner::effect(yields)]
+//! async fn ids(n: u32) -> u32 {
+//!     for i in 0..n {
+//!         yield i;
+//!     }
+//! }
+//!
+//! #[jenner::effect(yields)]
+//! async fn doubled(n: u32) -> u32 {
+//!     // error: `concurrent` needs the number of bodies to run at once, eg `concurrent(4)`
+//!     #[effect(async, concurrent)]
+//!     for x in ids(n) {
+//!         yield x * 2;
+//!     }
+//! }
+//! ```
+//!
+//! # Poll budget
+//!
+//! An async generator only returns to the executor when something it awaits is pending.
+//! If everything it awaits is always ready, like a for-loop over an in-memory stream,
+//! it can keep a single-threaded executor busy until it completes.
+//! `#[effect(yields, budget = n)]` counts the awaits, for-loop items and yields that did not suspend,
+//! and after `n` in a row the generator wakes itself and returns pending.
+//! [`yield_now`] does the same explicitly.
+//! A budgeted generator cannot also be `clone`.
+//!
+//! ```
+//! #![feature(generators)]
+//!
+//! use effective::{Async, Effective, EffectiveExt, Multiple};
+//! use jenner::effect;
+//! use std::convert::Infallible;
+//!
+//! #[effect(yields, budget = 64)]
+//! async fn squares(n: u64) -> u64 {
+//!     #[effect(async)]
+//!     for i in futures::stream::iter(0..n) {
+//!         yield i * i;
+//!     }
+//! }
+//!
+//! #[effect]
+//! async fn sum(
+//!     input: impl Effective<Item = u64, Failure = Infallible, Produces = Multiple, Async = Async>,
+//! ) -> u64 {
+//!     let mut total = 0;
+//!     #[effect(async)]
+//!     for x in input {
+//!         total += x;
+//!     }
+//!     total
+//! }
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() {
+//!     assert_eq!(sum(squares(1000)).shim().await, 332_833_500);
+//! }
+//! ```
+#![feature(generator_trait, async_iterator)]
 
 pub use effective;
 /// Apply to a function to convert it into an iterator, allowing the use of the `yield` keyword.
@@ -95,7 +402,6 @@ pub use effective;
 /// #![feature(generators)]
 ///
 /// use effective::{Effective, EffectiveExt, Async, Multiple};
-/// use effective::wrappers::future;
 /// use jenner::effect;
 /// use std::{time::{Instant, Duration}, convert::Infallible};
 ///
@@ -105,7 +411,7 @@ pub use effective;
 /// async fn countdown() -> u32 {
 ///     yield 5;
 ///     for i in (0..5).rev() {
-///         future(tokio::time::sleep(Duration::from_millis(200))).await;
+///         tokio::time::sleep(Duration::from_millis(200)).await;
 ///         yield i;
 ///     }
 /// }
@@ -171,8 +477,363 @@ pub use effective;
 /// ```
 pub use jenner_macro::effect;
 
+/// Runs an `#[effect]` function as a test, on a built-in executor that parks the thread while it is pending.
+///
+/// The function can be sync or async, and accepts the `fallible` effect. A failure fails the test,
+/// reporting the error with its [`Debug`](std::fmt::Debug) output.
+/// `timeout = ms` fails the test if it is still pending after that many milliseconds.
+/// The timeout is only checked while the effect is pending, so it cannot interrupt a body that blocks the thread.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// #[jenner::effect(fallible)]
+/// fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+///     s.parse::<u32>()?
+/// }
+///
+/// #[jenner::test(fallible, timeout = 1000)]
+/// async fn parses() -> Result<(), std::num::ParseIntError> {
+///     jenner::yield_now().await;
+///     let x = parse("42").await;
+///     assert_eq!(x, 42);
+/// }
+/// ```
+pub use jenner_macro::test;
+
+/// Runs an `#[effect]` function as the program's entry point, on the same executor as [`#[jenner::test]`](test).
+///
+/// Accepts the same options. If the effect fails or times out, the error is printed and the program exits with code 1.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use futures::channel::oneshot;
+/// use std::thread;
+///
+/// #[jenner::main(timeout = 5000)]
+/// async fn main() {
+///     let (tx, rx) = oneshot::channel();
+///     thread::spawn(move || tx.send(42).unwrap());
+///     let x = rx.await.unwrap();
+///     assert_eq!(x, 42);
+/// }
+/// ```
+pub use jenner_macro::main;
+
 mod asynch;
+mod block;
+mod bridge;
+mod budget;
+mod cancel;
+mod concurrent;
+mod convert;
+pub mod coop;
+mod demux;
+mod entry;
+mod lending;
+mod lift;
+mod next;
+mod noop;
+mod output;
+mod resume;
+mod scope;
+mod select;
+mod share;
 mod sync;
+mod uses;
+
+pub use block::{block_iter, block_on, try_block_iter, try_block_on, BlockIter, TryBlockIter};
+pub use bridge::{offload, pump, Offload, Pump, Pumped};
+pub use budget::{yield_now, YieldNow};
+pub use cancel::{Cancel, Cancellable, Cancelled, DeadlineElapsed};
+pub use demux::{DemuxOutput, DEMUX_CAPACITY};
+pub use lending::{LendingEffective, Lent, LentItem};
+pub use next::{next, try_next, Next, TryNext};
+pub use output::{EffectiveReturn, IntoOutput};
+pub use resume::{EffectiveResume, ResumeWith};
+pub use scope::{JoinHandle, Scope};
+pub use share::{lag, share, share_with, Lagged, Shared, SHARE_CAPACITY};
+pub use uses::{handle, Handled, Handler, Handlers, Operation, Operations, Uses};
+
+/// Yields an error from a `#[effect(yields, fallible(recoverable))]` generator without completing it.
+///
+/// Recoverable generators yield `Result`s. Errors from `yield_err!` are `Err` items,
+/// while errors that are returned, or propagated with `?`, are the terminal [`Failure`](effective::Failure).
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::{effect, yield_err};
+/// use std::pin::pin;
+///
+/// #[effect(yields, fallible(recoverable))]
+/// fn records(input: &'static str) -> Result<u32, String> {
+///     for line in input.lines() {
+///         if line == "EOF" {
+///             return Err("unexpected EOF".to_owned());
+///         }
+///         match line.parse() {
+///             Ok(x) => yield x,
+///             Err(_) => yield_err!(format!("bad record {line:?}")),
+///         }
+///     }
+/// }
+///
+/// #[effect(yields)]
+/// fn sum(input: &'static str) -> Result<u32, String> {
+///     let mut sum = 0;
+///     let mut records = pin!(records(input));
+///     // `Err(_)` is the terminal failure, `Ok(Some(Err(_)))` is a recoverable error
+///     while let Ok(Some(record)) = jenner::try_next(&mut records).await {
+///         match record {
+///             Ok(x) => sum += x,
+///             Err(e) => yield Err(e),
+///         }
+///     }
+///     yield Ok(sum);
+/// }
+///
+/// fn main() {
+///     let v: Vec<_> = pin!(sum("1\nx\n2\nEOF\n4")).shim().collect();
+///     assert_eq!(v, vec![Err("bad record \"x\"".to_owned()), Ok(3)]);
+/// }
+/// ```
+#[macro_export]
+macro_rules! yield_err {
+    ($err:expr) => {
+        ::core::compile_error!(
+            "`yield_err!` can only be used in `#[effect(yields, fallible(recoverable))]` functions"
+        )
+    };
+}
+
+/// Performs an [`Operation`] from an `#[effect(uses(...))]` body, suspending it until the operation is handled.
+/// Evaluates to the [`Operation::Output`]. See [`Operation`] for an example
+#[macro_export]
+macro_rules! perform {
+    ($op:expr) => {
+        ::core::compile_error!("`perform!` can only be used in `#[effect(uses(...))]` functions")
+    };
+}
+
+/// Registers async cleanup for a `#[effect(cancellable)]` function, run if it is cancelled
+/// at a later `.await` in the same scope. See [`Cancellable`] for an example
+#[macro_export]
+macro_rules! on_cancel {
+    ($($cleanup:tt)*) => {
+        ::core::compile_error!(
+            "`on_cancel!` can only be used in `#[effect(cancellable)]` functions"
+        )
+    };
+}
+
+/// Waits on several effects at once in an async `#[effect]` body,
+/// running the handler of the first branch to complete.
+///
+/// Each branch is `pattern = effect => handler`. The effect can be an [`Effective`](effective::Effective)
+/// or a future. Streams ([`Multiple`](effective::Multiple) effects) resolve to their next item,
+/// or `None` once they are done, so they are usually passed by reference with `.as_mut()`.
+///
+/// If the output does not match the pattern, that branch is disabled and the others keep running.
+/// A branch can also be disabled up front with a guard, `pattern = effect, if condition => handler`,
+/// eg to stop polling a stream that has finished. The effect of a disabled branch is still created,
+/// but never polled.
+/// Once every branch is disabled, the optional `else => handler` branch runs,
+/// or the body panics without one.
+///
+/// Each `select!` polls its branches starting from a different one each time it runs,
+/// so that none are starved.
+/// Start with `biased;` to always poll them in order.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::{pin::pin, time::Duration};
+///
+/// #[effect(yields)]
+/// async fn ticks() -> u32 {
+///     for i in 0.. {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         yield i;
+///     }
+/// }
+///
+/// /// Counts the ticks until the deadline
+/// #[effect]
+/// async fn count_ticks(deadline: Duration) -> u32 {
+///     let mut ticks = pin!(ticks());
+///     let mut deadline = pin!(tokio::time::sleep(deadline));
+///     let mut count = 0;
+///     loop {
+///         jenner::select! {
+///             biased;
+///             () = deadline.as_mut() => break count,
+///             Some(_) = ticks.as_mut() => count += 1,
+///         }
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let count = count_ticks(Duration::from_millis(55)).shim().await;
+///     assert_eq!(count, 5);
+/// }
+/// ```
+#[macro_export]
+macro_rules! select {
+    ($($branches:tt)*) => {
+        ::core::compile_error!("`select!` can only be used in async `#[effect]` functions")
+    };
+}
+
+/// Awaits several effects concurrently in an async `#[effect]` body,
+/// resolving to a tuple of their items once they have all completed.
+///
+/// Each effect can be an [`Effective`](effective::Effective) or a future, like with `.await`.
+/// `join!` waits for every effect before propagating the first failure,
+/// while [`try_join!`] propagates a failure as soon as it happens, dropping the other effects.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::time::Duration;
+///
+/// #[effect]
+/// async fn fetch(id: u32) -> u32 {
+///     tokio::time::sleep(Duration::from_millis(100)).await;
+///     id * 10
+/// }
+///
+/// #[effect]
+/// async fn fetch_both() -> (u32, u32) {
+///     jenner::join!(fetch(1), fetch(2)).await
+/// }
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let start = tokio::time::Instant::now();
+///     assert_eq!(fetch_both().shim().await, (10, 20));
+///     assert!(start.elapsed() < Duration::from_millis(200));
+/// }
+/// ```
+#[macro_export]
+macro_rules! join {
+    ($($effects:tt)*) => {
+        ::core::compile_error!("`join!` must be awaited in an async `#[effect]` function")
+    };
+}
+
+/// Like [`join!`], but propagates the first failure without waiting for the other effects.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::time::Duration;
+///
+/// #[effect(fallible)]
+/// async fn fetch(id: u32) -> Result<u32, &'static str> {
+///     tokio::time::sleep(Duration::from_millis(100 * id as u64)).await;
+///     if id == 1 {
+///         return Err("not found");
+///     }
+///     id * 10
+/// }
+///
+/// #[effect(fallible)]
+/// async fn fetch_both() -> Result<(u32, u32), &'static str> {
+///     jenner::try_join!(fetch(1), fetch(5)).await
+/// }
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let start = tokio::time::Instant::now();
+///     assert_eq!(fetch_both().shim().await, Err("not found"));
+///     assert!(start.elapsed() < Duration::from_millis(500));
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_join {
+    ($($effects:tt)*) => {
+        ::core::compile_error!("`try_join!` must be awaited in an async `#[effect]` function")
+    };
+}
+
+/// `scope!(|s| body)` runs `body` with a [`Scope`] that child effects can be spawned into,
+/// in an async `#[effect]` body.
+///
+/// The body is lowered into the surrounding generator, so it can `yield`, `.await`
+/// and return early like the rest of the function. This is why `scope` is a macro:
+/// the body of a closure passed to a function could not suspend the function around it.
+/// Whenever the body is waiting on an effect or resumed after a `yield`,
+/// the children are polled too, and they are all joined before the scope exits.
+/// That includes leaving the body early with `return`, `?`, `break` or `continue`.
+///
+/// Unlike spawning onto a runtime, the children can borrow from the function,
+/// but they only make progress while the function is being polled.
+/// The scope is single threaded, so an effect that awaits inside a scope is not `Send`.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::time::Duration;
+///
+/// #[effect]
+/// async fn fetch_page(n: u32) -> Vec<u32> {
+///     tokio::time::sleep(Duration::from_millis(50)).await;
+///     (n * 3..n * 3 + 3).collect()
+/// }
+///
+/// /// Fetches the next page while the current one is processed
+/// #[effect(yields)]
+/// async fn items(pages: u32) -> u32 {
+///     jenner::scope!(|s| {
+///         let mut next = Some(s.spawn(fetch_page(0)));
+///         let mut n = 0;
+///         while let Some(page) = next.take() {
+///             let page = page.await;
+///             n += 1;
+///             if n < pages {
+///                 next = Some(s.spawn(fetch_page(n)));
+///             }
+///             for item in page {
+///                 tokio::time::sleep(Duration::from_millis(10)).await;
+///                 yield item;
+///             }
+///         }
+///     })
+/// }
+///
+/// #[effect]
+/// async fn collect(pages: u32) -> Vec<u32> {
+///     let mut all = vec![];
+///     #[effect(async)]
+///     for item in items(pages) {
+///         all.push(item);
+///     }
+///     all
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(collect(2).shim().await, [0, 1, 2, 3, 4, 5]);
+/// }
+/// ```
+#[macro_export]
+macro_rules! scope {
+    ($($body:tt)*) => {
+        ::core::compile_error!("`scope!` can only be used in async `#[effect]` functions")
+    };
+}
 
 #[doc(hidden)]
 pub mod __private {
@@ -180,7 +841,41 @@ pub mod __private {
         AsyncFallibleGeneratorImpl, AsyncFallibleImpl, AsyncGeneratorImpl, AsyncImpl,
         UnsafeContextRef,
     };
+    pub use crate::block::{await_parked, park_on};
+    pub use crate::bridge::PumpedItem;
+    pub use crate::budget::Budget;
+    pub use crate::cancel::{CancelContext, CancelSuspend, CancellableImpl};
+    pub use crate::concurrent::{ConcurrentFor, MergeFailure, Owned};
+    pub use crate::convert::{
+        AsyncIterEffect, Await, AwaitEffective, AwaitFuture, FutureEffect, IntoEffective,
+        IntoEffectiveAsyncIter, IntoEffectiveEffect, IntoEffectiveIter, IntoEffectiveStream,
+        IterEffect, StreamEffect,
+    };
+    pub use crate::demux::{Demux, SharedFailure};
+    pub use crate::entry::{run_main, run_test, EntryFailure};
+    pub use crate::lending::{
+        AsyncFallibleLendingGeneratorImpl, AsyncLendingGeneratorImpl, IntoLent,
+        SyncFallibleLendingGeneratorImpl, SyncLendingGeneratorImpl,
+    };
+    pub use crate::lift::{
+        await_blocking, blocking, infallible, Captures, Delegate, Delegation, FailureInto, Lift,
+        LiftAsync, LiftFailure,
+    };
+    pub use crate::noop::noop_waker;
+    pub use crate::output::{TakeOutput, TakeOutputReturn, TakeOutputUnit};
+    pub use crate::resume::{
+        resumed, AsyncFallibleResumeGeneratorImpl, AsyncResumeGeneratorImpl, ResumeContext,
+        SyncFallibleResumeGeneratorImpl, SyncResumeGeneratorImpl,
+    };
+    pub use crate::select::{
+        poll_branch, select_start, BranchOutput, BranchResult, SelectBranch, SelectEffective,
+        SelectFuture,
+    };
     pub use crate::sync::{SyncFallibleGeneratorImpl, SyncGeneratorImpl};
-    // pub use std::future::{Future, IntoFuture};
+    pub use crate::uses::{
+        request, Choice, Contains, FallibleStream, FallibleTask, Here, Index, Mode,
+        PendingResponse, PerformContext, Provides, Stream, Suspend, Task, There, UsesImpl,
+    };
+    pub use pin_project_lite::pin_project;
     pub use std::{pin, task};
 }
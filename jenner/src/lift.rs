@@ -0,0 +1,218 @@
+use std::{convert::Infallible, marker::PhantomData, pin::Pin, task::Context};
+
+use effective::{
+    Async, Blocking, EffectResult, Effective, EffectiveResult, Failure, Multiple, Single,
+};
+
+use crate::EffectiveReturn;
+
+/// Lifts the failure of an effect into the failure of a fallible body.
+///
+/// Infallible effects can be used anywhere a fallible effect is expected.
+#[doc(hidden)]
+pub trait FailureInto<E> {
+    fn failure_into(self) -> E;
+}
+
+impl<E> FailureInto<E> for Infallible {
+    fn failure_into(self) -> E {
+        match self {}
+    }
+}
+
+impl<E> FailureInto<E> for Failure<E> {
+    fn failure_into(self) -> E {
+        self.0
+    }
+}
+
+/// Used by infallible bodies. Fails to compile if a fallible effect is used.
+#[doc(hidden)]
+pub fn infallible(failure: Infallible) -> ! {
+    match failure {}
+}
+
+/// Lets the `impl Trait` returned by an `#[effect]` function capture the lifetime of a reference argument
+#[doc(hidden)]
+pub trait Captures<'a> {}
+
+impl<'a, T: ?Sized> Captures<'a> for T {}
+
+/// Used by synchronous bodies. Fails to compile if an asynchronous effect is used.
+#[doc(hidden)]
+pub fn blocking(pending: Blocking) -> ! {
+    match pending {}
+}
+
+/// Used by `.await` in synchronous bodies.
+/// Fails to compile if the effect is asynchronous or produces several items.
+#[doc(hidden)]
+pub fn await_blocking<E>(effect: Pin<&mut E>) -> Result<E::Item, E::Failure>
+where
+    E: Effective<Produces = Single, Async = Blocking> + ?Sized,
+{
+    let waker = crate::noop::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    match effect.poll_effect(&mut cx) {
+        EffectResult::Item(x) => Ok(x),
+        EffectResult::Failure(x) => Err(x),
+        EffectResult::Pending(pending) => blocking(pending),
+        EffectResult::Done(Single) => unreachable!("single effects produce an item"),
+    }
+}
+
+/// Describes how many items an effect produces, so that it can be delegated to from a for-loop.
+#[doc(hidden)]
+pub trait Delegation {
+    const SINGLE: bool;
+}
+
+impl Delegation for Single {
+    const SINGLE: bool = true;
+}
+
+impl Delegation for Multiple {
+    const SINGLE: bool = false;
+}
+
+pin_project_lite::pin_project!(
+    /// Lifts any effect into a [`Multiple`] effect.
+    /// A [`Single`] effect is treated as a stream of exactly one item.
+    #[doc(hidden)]
+    pub struct Delegate<E> {
+        #[pin]
+        effect: E,
+        done: bool,
+    }
+);
+
+impl<E> Delegate<E> {
+    #[doc(hidden)]
+    pub fn new(effect: E) -> Self {
+        Self {
+            effect,
+            done: false,
+        }
+    }
+}
+
+impl<E> Effective for Delegate<E>
+where
+    E: Effective,
+    E::Produces: Delegation,
+{
+    type Item = E::Item;
+    type Failure = E::Failure;
+    type Produces = Multiple;
+    type Async = E::Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        if *this.done {
+            return EffectResult::Done(Multiple);
+        }
+        match this.effect.poll_effect(cx) {
+            EffectResult::Item(x) => {
+                *this.done = E::Produces::SINGLE;
+                EffectResult::Item(x)
+            }
+            EffectResult::Failure(x) => EffectResult::Failure(x),
+            EffectResult::Done(_) => {
+                *this.done = true;
+                EffectResult::Done(Multiple)
+            }
+            EffectResult::Pending(x) => EffectResult::Pending(x),
+        }
+    }
+}
+
+impl<E> EffectiveReturn for Delegate<E>
+where
+    E: EffectiveReturn,
+{
+    type Output = E::Output;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<Self::Output> {
+        self.project().effect.take_output()
+    }
+}
+
+/// Lifts the failure of a `dispatch` branch into the failure of the function.
+/// Infallible branches can be returned from fallible functions.
+#[doc(hidden)]
+pub trait LiftFailure<F> {
+    fn lift_failure(self) -> F;
+}
+
+impl<F> LiftFailure<F> for Infallible {
+    fn lift_failure(self) -> F {
+        match self {}
+    }
+}
+
+impl<E> LiftFailure<Failure<E>> for Failure<E> {
+    fn lift_failure(self) -> Failure<E> {
+        self
+    }
+}
+
+/// Lifts the pending state of a `dispatch` branch into that of the function.
+/// Blocking branches can be returned from async functions.
+#[doc(hidden)]
+pub trait LiftAsync<A> {
+    fn lift_async(self) -> A;
+}
+
+impl<A> LiftAsync<A> for Blocking {
+    fn lift_async(self) -> A {
+        match self {}
+    }
+}
+
+impl LiftAsync<Async> for Async {
+    fn lift_async(self) -> Async {
+        self
+    }
+}
+
+pin_project_lite::pin_project!(
+    /// Lifts an effect into one that fails with `F` and is pending with `A`,
+    /// so the branches of a `dispatch` function unify
+    #[doc(hidden)]
+    pub struct Lift<E, F, A> {
+        #[pin]
+        effect: E,
+        _marker: PhantomData<fn() -> (F, A)>,
+    }
+);
+
+impl<E, F, A> Lift<E, F, A> {
+    #[doc(hidden)]
+    pub fn new(effect: E) -> Self {
+        Self {
+            effect,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E, F, A> Effective for Lift<E, F, A>
+where
+    E: Effective,
+    E::Failure: LiftFailure<F>,
+    E::Async: LiftAsync<A>,
+{
+    type Item = E::Item;
+    type Failure = F;
+    type Produces = E::Produces;
+    type Async = A;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.project().effect.poll_effect(cx) {
+            EffectResult::Item(x) => EffectResult::Item(x),
+            EffectResult::Failure(x) => EffectResult::Failure(x.lift_failure()),
+            EffectResult::Done(x) => EffectResult::Done(x),
+            EffectResult::Pending(x) => EffectResult::Pending(x.lift_async()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{convert::Infallible, ops::DerefMut, pin::Pin, task::Context};
+
+use effective::{EffectResult, Effective, EffectiveResult, Failure, Multiple, Single};
+
+/// Creates an effect that resolves to the next item of the given [`Multiple`] effect,
+/// or `None` once it has completed.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::effect;
+/// use std::pin::pin;
+///
+/// #[effect(yields)]
+/// async fn count() -> u32 {
+///     yield 1;
+///     yield 2;
+/// }
+///
+/// #[effect]
+/// async fn sum() -> u32 {
+///     let mut a = pin!(count());
+///     let mut b = pin!(count());
+///     let mut total = 0;
+///     while let Some(x) = jenner::next(&mut a).await {
+///         total += x;
+///         if let Some(y) = jenner::next(&mut b).await {
+///             total += y * 10;
+///         }
+///     }
+///     total
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(sum().shim().await, 33);
+/// }
+/// ```
+pub fn next<P, E>(effect: &mut Pin<P>) -> Next<'_, E>
+where
+    P: DerefMut<Target = E>,
+    E: Effective<Produces = Multiple, Failure = Infallible> + ?Sized,
+{
+    Next {
+        effect: effect.as_mut(),
+    }
+}
+
+/// Creates an effect that resolves to the next item of the given fallible [`Multiple`] effect.
+///
+/// Resolves to `Ok(None)` once the effect has completed and `Err(e)` if it failed.
+pub fn try_next<P, E, Err>(effect: &mut Pin<P>) -> TryNext<'_, E>
+where
+    P: DerefMut<Target = E>,
+    E: Effective<Produces = Multiple, Failure = Failure<Err>> + ?Sized,
+{
+    TryNext {
+        effect: effect.as_mut(),
+    }
+}
+
+/// Effect returned by [`next`]
+#[must_use = "effects do nothing unless awaited"]
+pub struct Next<'a, E: ?Sized> {
+    effect: Pin<&'a mut E>,
+}
+
+/// Effect returned by [`try_next`]
+#[must_use = "effects do nothing unless awaited"]
+pub struct TryNext<'a, E: ?Sized> {
+    effect: Pin<&'a mut E>,
+}
+
+impl<E> Effective for Next<'_, E>
+where
+    E: Effective<Produces = Multiple, Failure = Infallible> + ?Sized,
+{
+    type Item = Option<E::Item>;
+    type Failure = Infallible;
+    type Produces = Single;
+    type Async = E::Async;
+
+    fn poll_effect(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.effect.as_mut().poll_effect(cx) {
+            EffectResult::Item(x) => EffectResult::Item(Some(x)),
+            EffectResult::Failure(x) => match x {},
+            EffectResult::Done(Multiple) => EffectResult::Item(None),
+            EffectResult::Pending(x) => EffectResult::Pending(x),
+        }
+    }
+}
+
+impl<E, Err> Effective for TryNext<'_, E>
+where
+    E: Effective<Produces = Multiple, Failure = Failure<Err>> + ?Sized,
+{
+    type Item = Result<Option<E::Item>, Err>;
+    type Failure = Infallible;
+    type Produces = Single;
+    type Async = E::Async;
+
+    fn poll_effect(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        match self.effect.as_mut().poll_effect(cx) {
+            EffectResult::Item(x) => EffectResult::Item(Ok(Some(x))),
+            EffectResult::Failure(Failure(e)) => EffectResult::Item(Err(e)),
+            EffectResult::Done(Multiple) => EffectResult::Item(Ok(None)),
+            EffectResult::Pending(x) => EffectResult::Pending(x),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use std::task::{RawWaker, RawWakerVTable, Waker};
+
+const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+fn clone(_: *const ()) -> RawWaker {
+    RawWaker::new(std::ptr::null(), &VTABLE)
+}
+
+fn noop(_: *const ()) {}
+
+/// A waker that does nothing. Used to poll [`Blocking`](effective::Blocking) effects
+/// from synchronous bodies, which never return pending.
+#[doc(hidden)]
+pub fn noop_waker() -> Waker {
+    unsafe { Waker::from_raw(clone(std::ptr::null())) }
+}
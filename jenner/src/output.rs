@@ -0,0 +1,125 @@
+use std::{pin::Pin, task::Context};
+
+use effective::{EffectResult, Effective, EffectiveResult, Multiple, Single};
+
+/// A [`Multiple`] effect that also completes with a final value once all items are produced.
+///
+/// Created with `#[effect(yields = Item)]`, where the function's return type is the final value.
+///
+/// ```
+/// #![feature(generators, stmt_expr_attributes)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::{effect, EffectiveReturn};
+///
+/// #[effect(yields = u8)]
+/// async fn bytes() -> usize {
+///     let mut count = 0;
+///     for b in *b"hello" {
+///         yield b;
+///         count += 1;
+///     }
+///     count
+/// }
+///
+/// #[effect]
+/// async fn total() -> (Vec<u8>, Option<usize>) {
+///     let mut v = vec![];
+///     // for-loops over an `EffectiveReturn` that initialize a `let` evaluate to the final value,
+///     // or `None` if the loop was exited early. Anywhere else they evaluate to `()`
+///     let count = #[effect(async)]
+///     for b in bytes() {
+///         v.push(b);
+///     };
+///     (v, count)
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(total().shim().await, (b"hello".to_vec(), Some(5)));
+///     assert_eq!(bytes().into_output().shim().await, 5);
+/// }
+/// ```
+pub trait EffectiveReturn: Effective<Produces = Multiple> {
+    /// The final value of the effect
+    type Output;
+
+    /// Takes the final value, if the effect has completed.
+    ///
+    /// Returns `None` if the effect has not yet completed, or if the value was already taken.
+    fn take_output(self: Pin<&mut Self>) -> Option<Self::Output>;
+
+    /// Discards all the items of this effect, resolving to the final value
+    fn into_output(self) -> IntoOutput<Self>
+    where
+        Self: Sized,
+    {
+        IntoOutput { effect: self }
+    }
+}
+
+pin_project_lite::pin_project!(
+    /// Effect returned by [`EffectiveReturn::into_output`]
+    #[must_use = "effects do nothing unless awaited"]
+    pub struct IntoOutput<E> {
+        #[pin]
+        effect: E,
+    }
+);
+
+impl<E> Effective for IntoOutput<E>
+where
+    E: EffectiveReturn,
+{
+    type Item = E::Output;
+    type Failure = E::Failure;
+    type Produces = Single;
+    type Async = E::Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let mut effect = self.project().effect;
+        loop {
+            match effect.as_mut().poll_effect(cx) {
+                EffectResult::Item(_) => continue,
+                EffectResult::Failure(x) => return EffectResult::Failure(x),
+                EffectResult::Done(Multiple) => {
+                    let output = effect.take_output();
+                    return EffectResult::Item(output.expect("output should be set once done"));
+                }
+                EffectResult::Pending(x) => return EffectResult::Pending(x),
+            }
+        }
+    }
+}
+
+/// Wrapper used to pick the value of an `#[effect(async)]` for-loop.
+///
+/// Uses auto-ref specialisation: resolves to [`TakeOutputReturn`] for [`EffectiveReturn`]s,
+/// falling back to [`TakeOutputUnit`] which evaluates to `()`.
+#[doc(hidden)]
+pub struct TakeOutput<P>(pub P);
+
+#[doc(hidden)]
+pub trait TakeOutputReturn {
+    type Output;
+    fn take_output(&mut self) -> Self::Output;
+}
+
+impl<E> TakeOutputReturn for TakeOutput<Pin<&mut E>>
+where
+    E: EffectiveReturn + ?Sized,
+{
+    type Output = Option<E::Output>;
+    fn take_output(&mut self) -> Self::Output {
+        self.0.as_mut().take_output()
+    }
+}
+
+#[doc(hidden)]
+pub trait TakeOutputUnit {
+    fn take_output(&mut self);
+}
+
+impl<P> TakeOutputUnit for &mut TakeOutput<P> {
+    fn take_output(&mut self) {}
+}
@@ -0,0 +1,510 @@
+use std::{
+    convert::Infallible,
+    ops::{Generator, GeneratorState},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use effective::{
+    Async, Blocking, EffectResult, Effective, EffectiveResult, Failure, Multiple, Single,
+};
+
+use crate::{asynch::UnsafeContextRef, EffectiveReturn};
+
+/// A [`Multiple`] effect that can receive values each time it is resumed after yielding.
+///
+/// Created with `#[effect(yields, resumes(T))]`. Within the body, `yield` evaluates to `Some(value)`
+/// with the value passed to [`resume_with`](EffectiveResume::resume_with), or to `None` if the effect
+/// was polled without one, like by [`jenner::next`](crate::next) or a for-loop. In fallible bodies,
+/// an error passed to [`throw`](EffectiveResume::throw) is returned from the `yield` as if by `?`.
+///
+/// A value passed before the first `yield`, or while an async body is pending on an `.await`,
+/// is kept for the next `yield` rather than dropped, unless another value replaces it first.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::{effect, EffectiveResume};
+/// use std::pin::pin;
+///
+/// /// Yields the running total of all values sent to it
+/// #[effect(yields, resumes(u32))]
+/// fn running_total() -> u32 {
+///     let mut total = 0;
+///     let mut sent = yield total;
+///     while let Some(x) = sent {
+///         total += x;
+///         sent = yield total;
+///     }
+/// }
+///
+/// #[effect]
+/// async fn drive() -> Vec<u32> {
+///     let mut gen = pin!(running_total());
+///     let mut v = vec![jenner::next(&mut gen).await.unwrap()];
+///     for i in 1..=3 {
+///         v.push(gen.as_mut().resume_with(i).await.unwrap());
+///     }
+///     v
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(drive().shim().await, vec![0, 1, 3, 6]);
+/// }
+/// ```
+pub trait EffectiveResume: Effective<Produces = Multiple> {
+    /// The value that `yield` evaluates to
+    type Resume;
+    /// The error that can be thrown into the effect
+    type Throw;
+
+    /// Sets the value that the suspended `yield` evaluates to when the effect is next polled
+    fn set_resume(self: Pin<&mut Self>, value: Result<Self::Resume, Self::Throw>);
+
+    /// Resumes the effect with a value, resolving to the next item,
+    /// or `None` once it has completed.
+    fn resume_with(self: Pin<&mut Self>, value: Self::Resume) -> ResumeWith<'_, Self> {
+        ResumeWith {
+            effect: self,
+            value: Some(Ok(value)),
+        }
+    }
+
+    /// Resumes the effect with an error, resolving to the next item,
+    /// or `None` once it has completed.
+    fn throw(self: Pin<&mut Self>, err: Self::Throw) -> ResumeWith<'_, Self> {
+        ResumeWith {
+            effect: self,
+            value: Some(Err(err)),
+        }
+    }
+}
+
+/// Effect returned by [`EffectiveResume::resume_with`] and [`EffectiveResume::throw`]
+#[must_use = "effects do nothing unless awaited"]
+pub struct ResumeWith<'a, E: EffectiveResume + ?Sized> {
+    effect: Pin<&'a mut E>,
+    value: Option<Result<E::Resume, E::Throw>>,
+}
+
+impl<E: EffectiveResume + ?Sized> Unpin for ResumeWith<'_, E> {}
+
+impl<E> Effective for ResumeWith<'_, E>
+where
+    E: EffectiveResume + ?Sized,
+{
+    type Item = Option<E::Item>;
+    type Failure = E::Failure;
+    type Produces = Single;
+    type Async = E::Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.get_mut();
+        if let Some(value) = this.value.take() {
+            this.effect.as_mut().set_resume(value);
+        }
+        match this.effect.as_mut().poll_effect(cx) {
+            EffectResult::Item(x) => EffectResult::Item(Some(x)),
+            EffectResult::Failure(x) => EffectResult::Failure(x),
+            EffectResult::Done(Multiple) => EffectResult::Item(None),
+            EffectResult::Pending(x) => EffectResult::Pending(x),
+        }
+    }
+}
+
+/// Resume argument of asynchronous resumable generators
+#[doc(hidden)]
+pub struct ResumeContext<T, E> {
+    cx: UnsafeContextRef,
+    value: Option<Result<T, E>>,
+}
+
+impl<T, E> ResumeContext<T, E> {
+    /// Used where the generator starts or resumes from an await,
+    /// which the resume value is never passed to
+    #[doc(hidden)]
+    pub fn into_context(self) -> UnsafeContextRef {
+        debug_assert!(
+            self.value.is_none(),
+            "resume values are only passed to a `yield`"
+        );
+        self.cx
+    }
+
+    #[doc(hidden)]
+    pub fn resumed(self) -> (UnsafeContextRef, Result<Option<T>, E>) {
+        (self.cx, resumed(self.value))
+    }
+}
+
+/// The value a `yield` evaluates to, `None` if the effect was polled without [`EffectiveResume::resume_with`]
+#[doc(hidden)]
+pub fn resumed<T, E>(value: Option<Result<T, E>>) -> Result<Option<T>, E> {
+    value.transpose()
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct SyncResumeGeneratorImpl<G, T, E, R> {
+        #[pin]
+        generator: G,
+        resume: Option<Result<T, E>>,
+        // whether the generator is suspended at a `yield`, which the resume value is for
+        yielded: bool,
+        output: Option<R>,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct SyncFallibleResumeGeneratorImpl<G, T, E, R> {
+        #[pin]
+        generator: G,
+        resume: Option<Result<T, E>>,
+        // whether the generator is suspended at a `yield`, which the resume value is for
+        yielded: bool,
+        output: Option<R>,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct AsyncResumeGeneratorImpl<G, T, E, R> {
+        #[pin]
+        generator: G,
+        resume: Option<Result<T, E>>,
+        // whether the generator is suspended at a `yield`, which the resume value is for
+        yielded: bool,
+        output: Option<R>,
+    }
+);
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct AsyncFallibleResumeGeneratorImpl<G, T, E, R> {
+        #[pin]
+        generator: G,
+        resume: Option<Result<T, E>>,
+        // whether the generator is suspended at a `yield`, which the resume value is for
+        yielded: bool,
+        output: Option<R>,
+    }
+);
+
+impl<G, T, R> SyncResumeGeneratorImpl<G, T, Infallible, R> {
+    #[doc(hidden)]
+    pub fn create<Y>(
+        generator: G,
+    ) -> impl EffectiveResume<
+        Resume = T,
+        Throw = Infallible,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Infallible,
+        Async = Blocking,
+    > + EffectiveReturn<Output = R>
+    where
+        G: Generator<Option<Result<T, Infallible>>, Yield = Y, Return = R>,
+    {
+        Self {
+            generator,
+            resume: None,
+            yielded: false,
+            output: None,
+        }
+    }
+}
+
+impl<G, T, E, R> SyncFallibleResumeGeneratorImpl<G, T, E, R> {
+    #[doc(hidden)]
+    pub fn create<Y>(
+        generator: G,
+    ) -> impl EffectiveResume<
+        Resume = T,
+        Throw = E,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Failure<E>,
+        Async = Blocking,
+    > + EffectiveReturn<Output = R>
+    where
+        G: Generator<Option<Result<T, E>>, Yield = Y, Return = Result<R, E>>,
+    {
+        Self {
+            generator,
+            resume: None,
+            yielded: false,
+            output: None,
+        }
+    }
+}
+
+impl<G, T, R> AsyncResumeGeneratorImpl<G, T, Infallible, R> {
+    #[doc(hidden)]
+    pub fn create<Y>(
+        generator: G,
+    ) -> impl EffectiveResume<
+        Resume = T,
+        Throw = Infallible,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Infallible,
+        Async = Async,
+    > + EffectiveReturn<Output = R>
+    where
+        G: Generator<ResumeContext<T, Infallible>, Yield = Poll<Y>, Return = R>,
+    {
+        Self {
+            generator,
+            resume: None,
+            yielded: false,
+            output: None,
+        }
+    }
+}
+
+impl<G, T, E, R> AsyncFallibleResumeGeneratorImpl<G, T, E, R> {
+    #[doc(hidden)]
+    pub fn create<Y>(
+        generator: G,
+    ) -> impl EffectiveResume<
+        Resume = T,
+        Throw = E,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Failure<E>,
+        Async = Async,
+    > + EffectiveReturn<Output = R>
+    where
+        G: Generator<ResumeContext<T, E>, Yield = Poll<Y>, Return = Result<R, E>>,
+    {
+        Self {
+            generator,
+            resume: None,
+            yielded: false,
+            output: None,
+        }
+    }
+}
+
+impl<G, T, R> Effective for SyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<Option<Result<T, Infallible>>, Return = R>,
+{
+    type Item = G::Yield;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    type Async = Blocking;
+
+    fn poll_effect(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        let value = if *this.yielded {
+            this.resume.take()
+        } else {
+            None
+        };
+        *this.yielded = true;
+        match this.generator.resume(value) {
+            GeneratorState::Yielded(x) => EffectResult::Item(x),
+            GeneratorState::Complete(r) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
+        }
+    }
+}
+
+impl<G, T, E, R> Effective for SyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<Option<Result<T, E>>, Return = Result<R, E>>,
+{
+    type Item = G::Yield;
+    type Failure = Failure<E>;
+    type Produces = Multiple;
+    type Async = Blocking;
+
+    fn poll_effect(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        let value = if *this.yielded {
+            this.resume.take()
+        } else {
+            None
+        };
+        *this.yielded = true;
+        match this.generator.resume(value) {
+            GeneratorState::Yielded(x) => EffectResult::Item(x),
+            GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
+            GeneratorState::Complete(Ok(r)) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
+        }
+    }
+}
+
+impl<G, Y, T, R> Effective for AsyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<ResumeContext<T, Infallible>, Yield = Poll<Y>, Return = R>,
+{
+    type Item = Y;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        let arg = ResumeContext {
+            cx: cx.into(),
+            value: if *this.yielded {
+                this.resume.take()
+            } else {
+                None
+            },
+        };
+        match this.generator.resume(arg) {
+            GeneratorState::Yielded(Poll::Ready(x)) => {
+                *this.yielded = true;
+                EffectResult::Item(x)
+            }
+            GeneratorState::Yielded(Poll::Pending) => {
+                *this.yielded = false;
+                EffectResult::Pending(Async)
+            }
+            GeneratorState::Complete(r) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
+        }
+    }
+}
+
+impl<G, Y, T, E, R> Effective for AsyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<ResumeContext<T, E>, Yield = Poll<Y>, Return = Result<R, E>>,
+{
+    type Item = Y;
+    type Failure = Failure<E>;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        let arg = ResumeContext {
+            cx: cx.into(),
+            value: if *this.yielded {
+                this.resume.take()
+            } else {
+                None
+            },
+        };
+        match this.generator.resume(arg) {
+            GeneratorState::Yielded(Poll::Ready(x)) => {
+                *this.yielded = true;
+                EffectResult::Item(x)
+            }
+            GeneratorState::Yielded(Poll::Pending) => {
+                *this.yielded = false;
+                EffectResult::Pending(Async)
+            }
+            GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
+            GeneratorState::Complete(Ok(r)) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
+        }
+    }
+}
+
+impl<G, T, R> EffectiveResume for SyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<Option<Result<T, Infallible>>, Return = R>,
+{
+    type Resume = T;
+    type Throw = Infallible;
+
+    fn set_resume(self: Pin<&mut Self>, value: Result<T, Infallible>) {
+        *self.project().resume = Some(value);
+    }
+}
+
+impl<G, T, E, R> EffectiveResume for SyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<Option<Result<T, E>>, Return = Result<R, E>>,
+{
+    type Resume = T;
+    type Throw = E;
+
+    fn set_resume(self: Pin<&mut Self>, value: Result<T, E>) {
+        *self.project().resume = Some(value);
+    }
+}
+
+impl<G, Y, T, R> EffectiveResume for AsyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<ResumeContext<T, Infallible>, Yield = Poll<Y>, Return = R>,
+{
+    type Resume = T;
+    type Throw = Infallible;
+
+    fn set_resume(self: Pin<&mut Self>, value: Result<T, Infallible>) {
+        *self.project().resume = Some(value);
+    }
+}
+
+impl<G, Y, T, E, R> EffectiveResume for AsyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<ResumeContext<T, E>, Yield = Poll<Y>, Return = Result<R, E>>,
+{
+    type Resume = T;
+    type Throw = E;
+
+    fn set_resume(self: Pin<&mut Self>, value: Result<T, E>) {
+        *self.project().resume = Some(value);
+    }
+}
+
+impl<G, T, R> EffectiveReturn for SyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<Option<Result<T, Infallible>>, Return = R>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
+impl<G, T, E, R> EffectiveReturn for SyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<Option<Result<T, E>>, Return = Result<R, E>>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
+impl<G, Y, T, R> EffectiveReturn for AsyncResumeGeneratorImpl<G, T, Infallible, R>
+where
+    G: Generator<ResumeContext<T, Infallible>, Yield = Poll<Y>, Return = R>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
+impl<G, Y, T, E, R> EffectiveReturn for AsyncFallibleResumeGeneratorImpl<G, T, E, R>
+where
+    G: Generator<ResumeContext<T, E>, Yield = Poll<Y>, Return = Result<R, E>>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Single};
+
+/// Spawns child effects inside a [`scope!`](crate::scope!)
+///
+/// The children are polled along with the scope body whenever it is waiting on an effect
+/// or resumed after a `yield`, and are all joined before the scope exits.
+pub struct Scope<'s> {
+    children: RefCell<Vec<Pin<Box<dyn Child + 's>>>>,
+    /// Children spawned since the last poll, kept apart so children can spawn while being polled
+    spawned: RefCell<Vec<Pin<Box<dyn Child + 's>>>>,
+}
+
+#[doc(hidden)]
+impl Default for Scope<'_> {
+    fn default() -> Self {
+        Self {
+            children: RefCell::new(vec![]),
+            spawned: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl<'s> Scope<'s> {
+    /// Spawns a child effect into the scope, returning a handle to await its result
+    ///
+    /// The child runs even if the handle is dropped, and its result is then discarded.
+    pub fn spawn<E>(&self, effect: E) -> JoinHandle<E::Item, E::Failure>
+    where
+        E: Effective<Produces = Single> + 's,
+    {
+        let slot = Rc::new(RefCell::new(SlotState {
+            output: None,
+            waker: None,
+        }));
+        self.spawned.borrow_mut().push(Box::pin(ChildTask {
+            effect,
+            slot: slot.clone(),
+        }));
+        JoinHandle { slot: Some(slot) }
+    }
+
+    /// Polls every child once, returning whether any of them completed
+    #[doc(hidden)]
+    pub fn poll_children(&self, cx: &mut Context<'_>) -> bool {
+        let mut children = self.children.borrow_mut();
+        let mut completed = false;
+        let mut i = 0;
+        loop {
+            // children spawned while polling are polled in the same pass
+            children.append(&mut self.spawned.borrow_mut());
+            let child = match children.get_mut(i) {
+                Some(child) => child,
+                None => break completed,
+            };
+            if child.as_mut().poll_child(cx).is_ready() {
+                children.swap_remove(i);
+                completed = true;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Whether every child has completed
+    #[doc(hidden)]
+    pub fn is_empty(&self) -> bool {
+        self.children.borrow().is_empty() && self.spawned.borrow().is_empty()
+    }
+}
+
+type Slot<T, F> = Rc<RefCell<SlotState<T, F>>>;
+
+struct SlotState<T, F> {
+    output: Option<Result<T, F>>,
+    /// The task waiting on the [`JoinHandle`]
+    waker: Option<Waker>,
+}
+
+trait Child {
+    fn poll_child(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+pin_project_lite::pin_project!(
+    struct ChildTask<E, T, F> {
+        #[pin]
+        effect: E,
+        slot: Slot<T, F>,
+    }
+);
+
+impl<E> Child for ChildTask<E, E::Item, E::Failure>
+where
+    E: Effective<Produces = Single>,
+{
+    fn poll_child(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        let output = match this.effect.poll_effect(cx) {
+            EffectResult::Item(x) => Ok(x),
+            EffectResult::Failure(f) => Err(f),
+            EffectResult::Pending(_) => return Poll::Pending,
+            EffectResult::Done(_) => unreachable!("single effects never complete without an item"),
+        };
+        let mut slot = this.slot.borrow_mut();
+        slot.output = Some(output);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(())
+    }
+}
+
+/// Effect returned by [`Scope::spawn`], resolving to the output of the child effect
+#[must_use = "dropping the handle discards the result of the child effect"]
+pub struct JoinHandle<T, F> {
+    slot: Option<Slot<T, F>>,
+}
+
+impl<T, F> Effective for JoinHandle<T, F> {
+    type Item = T;
+    type Failure = F;
+    type Produces = Single;
+    type Async = Async;
+
+    fn poll_effect(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let slot = self
+            .slot
+            .as_ref()
+            .expect("join handle polled after it completed");
+        let mut slot = slot.borrow_mut();
+        let output = match slot.output.take() {
+            Some(output) => output,
+            None => {
+                // woken by the child once it completes
+                slot.waker = Some(cx.waker().clone());
+                return EffectResult::Pending(Async);
+            }
+        };
+        drop(slot);
+        self.slot = None;
+        match output {
+            Ok(x) => EffectResult::Item(x),
+            Err(f) => EffectResult::Failure(f),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    future::IntoFuture,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use effective::{EffectResult, Effective, Multiple, Single};
+
+use crate::convert::FutureEffect;
+
+/// Wrapper used to pick an effect conversion for `select!` branches.
+///
+/// Uses auto-ref specialisation like [`Await`](crate::__private::Await),
+/// but accepts [`Multiple`] effects as well, so streams can be selected over.
+#[doc(hidden)]
+pub struct SelectBranch<T>(Option<T>);
+
+impl<T> SelectBranch<T> {
+    #[doc(hidden)]
+    pub fn new(value: T) -> Self {
+        Self(Some(value))
+    }
+
+    fn take(&mut self) -> T {
+        self.0
+            .take()
+            .expect("select conversion should only be performed once")
+    }
+}
+
+#[doc(hidden)]
+pub trait SelectEffective {
+    type Effect;
+    fn into_branch(&mut self) -> Self::Effect;
+}
+
+impl<E: Effective> SelectEffective for SelectBranch<E> {
+    type Effect = E;
+    fn into_branch(&mut self) -> Self::Effect {
+        self.take()
+    }
+}
+
+#[doc(hidden)]
+pub trait SelectFuture {
+    type Effect;
+    fn into_branch(&mut self) -> Self::Effect;
+}
+
+impl<F: IntoFuture> SelectFuture for &mut SelectBranch<F> {
+    type Effect = FutureEffect<F::IntoFuture>;
+    fn into_branch(&mut self) -> Self::Effect {
+        FutureEffect::new(self.take().into_future())
+    }
+}
+
+/// What a `select!` branch resolves to.
+/// [`Single`] effects resolve to their item, and [`Multiple`] effects to their next item,
+/// or `None` once they are done.
+#[doc(hidden)]
+pub trait BranchOutput<T> {
+    type Output;
+    fn item(item: T) -> Self::Output;
+    fn done() -> Self::Output;
+}
+
+impl<T> BranchOutput<T> for Single {
+    type Output = T;
+    fn item(item: T) -> T {
+        item
+    }
+    fn done() -> T {
+        unreachable!("single effects never complete without an item")
+    }
+}
+
+impl<T> BranchOutput<T> for Multiple {
+    type Output = Option<T>;
+    fn item(item: T) -> Option<T> {
+        Some(item)
+    }
+    fn done() -> Option<T> {
+        None
+    }
+}
+
+#[doc(hidden)]
+pub type BranchResult<E> = Poll<
+    Result<
+        <<E as Effective>::Produces as BranchOutput<<E as Effective>::Item>>::Output,
+        <E as Effective>::Failure,
+    >,
+>;
+
+#[doc(hidden)]
+pub fn poll_branch<E>(effect: Pin<&mut E>, cx: &mut Context<'_>) -> BranchResult<E>
+where
+    E: Effective,
+    E::Produces: BranchOutput<E::Item>,
+{
+    match effect.poll_effect(cx) {
+        EffectResult::Item(x) => Poll::Ready(Ok(E::Produces::item(x))),
+        EffectResult::Done(_) => Poll::Ready(Ok(E::Produces::done())),
+        EffectResult::Failure(f) => Poll::Ready(Err(f)),
+        EffectResult::Pending(_) => Poll::Pending,
+    }
+}
+
+/// The branch that a fair `select!` polls first, rotating on every call.
+///
+/// Each `select!` has its own counter, so that several selects in one loop
+/// don't move each other's rotation.
+#[doc(hidden)]
+pub fn select_start(counter: &AtomicUsize, branches: usize) -> usize {
+    counter.fetch_add(1, Ordering::Relaxed) % branches
+}
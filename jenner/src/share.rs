@@ -0,0 +1,402 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex, TryLockError},
+    task::{Context, Wake, Waker},
+};
+
+use effective::{Async, EffectResult, Effective, EffectiveResult, Multiple};
+
+use crate::demux::{lock, Fanout, SharedFailure};
+
+/// How many items [`share`] buffers between its fastest and slowest consumers.
+pub const SHARE_CAPACITY: usize = 16;
+
+/// Shares a stream between several consumers, which each see every item.
+///
+/// Clone the returned [`Shared`] to create more consumers. A clone starts from the same item
+/// as the consumer it was cloned from. The stream is polled by whichever consumer needs the next item,
+/// and the others are woken once it arrives.
+///
+/// Up to [`SHARE_CAPACITY`] items are buffered for consumers that fall behind,
+/// after which the fastest consumer waits for the slowest to catch up.
+/// Use [`share_with`] to pick another capacity or [`lag`] policy.
+///
+/// If the stream fails, every consumer reports the failure after the items buffered for it,
+/// which requires a failure that can be cloned.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::{Async, Effective, EffectiveExt, Multiple};
+/// use jenner::effect;
+/// use std::convert::Infallible;
+///
+/// #[effect(yields)]
+/// async fn changes() -> u32 {
+///     for i in 0..5 {
+///         yield i;
+///     }
+/// }
+///
+/// #[effect]
+/// async fn collect(
+///     input: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+/// ) -> Vec<u32> {
+///     let mut v = vec![];
+///     #[effect(async)]
+///     for i in input {
+///         v.push(i);
+///     }
+///     v
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let a = jenner::share(changes());
+///     let b = a.clone();
+///     let (a, b) = tokio::join!(collect(a).shim(), collect(b).shim());
+///     assert_eq!(a, vec![0, 1, 2, 3, 4]);
+///     assert_eq!(b, vec![0, 1, 2, 3, 4]);
+/// }
+/// ```
+pub fn share<E>(effect: E) -> Shared<E>
+where
+    E: Effective<Produces = Multiple, Async = Async>,
+{
+    share_with(effect, SHARE_CAPACITY, lag::Block)
+}
+
+/// Shares a stream like [`share`], buffering up to `capacity` items
+/// and handling consumers that fall further behind with the [`lag`] policy `L`.
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::{effect, lag, Lagged};
+///
+/// #[effect(yields)]
+/// async fn changes() -> u32 {
+///     for i in 0..5 {
+///         yield i;
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut fast = std::pin::pin!(jenner::share_with(changes(), 2, lag::Fail));
+///     let mut slow = std::pin::pin!(fast.clone());
+///     for i in 0..5 {
+///         assert_eq!(jenner::try_next(&mut fast).shim().await, Ok(Some(i)));
+///     }
+///     // the slow consumer missed the items that no longer fit in the buffer
+///     assert_eq!(jenner::try_next(&mut slow).shim().await, Err(Lagged(3)));
+///     assert_eq!(jenner::try_next(&mut slow).shim().await, Ok(Some(3)));
+/// }
+/// ```
+pub fn share_with<E, L>(effect: E, capacity: usize, lag: L) -> Shared<E, L>
+where
+    E: Effective<Produces = Multiple, Async = Async>,
+    L: lag::Lag<E::Failure>,
+{
+    assert!(
+        capacity > 0,
+        "shared streams must be able to buffer an item"
+    );
+    let _ = lag;
+    Shared {
+        shared: Arc::new(Share {
+            source: Mutex::new(Some(Box::pin(effect))),
+            state: Mutex::new(ShareState {
+                buffer: VecDeque::new(),
+                head: 0,
+                cursors: vec![Some(0)],
+                capacity,
+                done: false,
+                failure: None,
+                reported: vec![false],
+            }),
+            wakers: Arc::new(Fanout(Mutex::new(vec![None]))),
+        }),
+        index: 0,
+        _lag: PhantomData,
+    }
+}
+
+/// What a shared stream does when a consumer falls more than its capacity behind the others
+pub mod lag {
+    use std::convert::Infallible;
+
+    use effective::Failure;
+
+    use super::Lagged;
+
+    /// A policy for consumers of a [`share_with`](crate::share_with) stream that fall behind.
+    /// `F` is the failure of the shared stream.
+    pub trait Lag<F> {
+        /// The failure of the consumers
+        type Failure;
+
+        /// Whether the oldest item is dropped when the buffer is full,
+        /// rather than waiting for the slowest consumer
+        const DROPS: bool;
+
+        /// Reports that a consumer missed some items, if the policy fails lagging consumers
+        fn lagged(lagged: Lagged) -> Option<Self::Failure>;
+
+        /// Converts the failure of the shared stream
+        fn failure(failure: F) -> Self::Failure;
+    }
+
+    /// Consumers wait for the slowest consumer to catch up, so none miss any items
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Block;
+
+    /// The oldest items are dropped, and lagging consumers silently skip ahead
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct DropOldest;
+
+    /// The oldest items are dropped, and lagging consumers fail with [`Lagged`] before skipping ahead.
+    /// The failure of the stream must be convertible from [`Lagged`].
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Fail;
+
+    impl<F> Lag<F> for Block {
+        type Failure = F;
+        const DROPS: bool = false;
+        fn lagged(_: Lagged) -> Option<F> {
+            unreachable!("blocking consumers never lag")
+        }
+        fn failure(failure: F) -> F {
+            failure
+        }
+    }
+
+    impl<F> Lag<F> for DropOldest {
+        type Failure = F;
+        const DROPS: bool = true;
+        fn lagged(_: Lagged) -> Option<F> {
+            None
+        }
+        fn failure(failure: F) -> F {
+            failure
+        }
+    }
+
+    impl Lag<Infallible> for Fail {
+        type Failure = Failure<Lagged>;
+        const DROPS: bool = true;
+        fn lagged(lagged: Lagged) -> Option<Self::Failure> {
+            Some(Failure(lagged))
+        }
+        fn failure(failure: Infallible) -> Self::Failure {
+            match failure {}
+        }
+    }
+
+    impl<E: From<Lagged>> Lag<Failure<E>> for Fail {
+        type Failure = Failure<E>;
+        const DROPS: bool = true;
+        fn lagged(lagged: Lagged) -> Option<Self::Failure> {
+            Some(Failure(lagged.into()))
+        }
+        fn failure(failure: Failure<E>) -> Self::Failure {
+            failure
+        }
+    }
+}
+
+/// A consumer of a [`lag::Fail`] stream fell behind, and missed this many items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lagged(pub u64);
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the consumer fell behind and missed {} items", self.0)
+    }
+}
+
+impl std::error::Error for Lagged {}
+
+/// A consumer of a stream shared with [`share`]
+pub struct Shared<E: Effective, L = lag::Block> {
+    shared: Arc<Share<E>>,
+    index: usize,
+    _lag: PhantomData<fn() -> L>,
+}
+
+struct Share<E: Effective> {
+    /// The stream, until it completes. Locked apart from the state,
+    /// so consumers can read the buffer while another one polls the stream
+    source: Mutex<Option<Pin<Box<E>>>>,
+    state: Mutex<ShareState<E>>,
+    wakers: Arc<Fanout>,
+}
+
+struct ShareState<E: Effective> {
+    buffer: VecDeque<E::Item>,
+    /// The position in the stream of the first buffered item
+    head: u64,
+    /// The position of the next item of each consumer, or `None` once it is dropped
+    cursors: Vec<Option<u64>>,
+    capacity: usize,
+    done: bool,
+    failure: Option<E::Failure>,
+    /// Whether each consumer has reported the failure
+    reported: Vec<bool>,
+}
+
+impl<E: Effective> ShareState<E> {
+    /// Drops the items every consumer has seen, returning whether any were dropped
+    fn prune(&mut self) -> bool {
+        let slowest = self.cursors.iter().flatten().min().copied();
+        let seen = slowest.map_or(self.buffer.len() as u64, |slowest| {
+            slowest.saturating_sub(self.head)
+        });
+        let seen = (seen as usize).min(self.buffer.len());
+        self.buffer.drain(..seen);
+        self.head += seen as u64;
+        seen > 0
+    }
+}
+
+impl<E: Effective, L> Clone for Shared<E, L> {
+    fn clone(&self) -> Self {
+        let mut state = lock(&self.shared.state);
+        let cursor = state.cursors[self.index];
+        let reported = state.reported[self.index];
+        let index = match state.cursors.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                state.cursors.push(None);
+                state.reported.push(false);
+                lock(&self.shared.wakers.0).push(None);
+                state.cursors.len() - 1
+            }
+        };
+        state.cursors[index] = cursor;
+        state.reported[index] = reported;
+        Self {
+            shared: self.shared.clone(),
+            index,
+            _lag: PhantomData,
+        }
+    }
+}
+
+impl<E, L> Effective for Shared<E, L>
+where
+    E: Effective<Produces = Multiple, Async = Async>,
+    E::Item: Clone,
+    E::Failure: SharedFailure,
+    L: lag::Lag<E::Failure>,
+{
+    type Item = E::Item;
+    type Failure = L::Failure;
+    type Produces = Multiple;
+    type Async = Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let index = self.index;
+
+        loop {
+            {
+                let mut state = lock(&self.shared.state);
+                let state = &mut *state;
+                let cursor = state.cursors[index].expect("consumers are only removed when dropped");
+                if cursor < state.head {
+                    // the items this consumer had not seen yet were dropped
+                    state.cursors[index] = Some(state.head);
+                    if let Some(failure) = L::lagged(Lagged(state.head - cursor)) {
+                        return EffectResult::Failure(failure);
+                    }
+                    continue;
+                }
+
+                if let Some(item) = state.buffer.get((cursor - state.head) as usize) {
+                    let item = item.clone();
+                    state.cursors[index] = Some(cursor + 1);
+                    if state.prune() {
+                        // consumers might have been waiting for the slowest to catch up
+                        self.shared.wakers.wake_by_ref();
+                    }
+                    return EffectResult::Item(item);
+                }
+
+                if state.done {
+                    let reported = std::mem::replace(&mut state.reported[index], true);
+                    return match &state.failure {
+                        Some(failure) if !reported => {
+                            EffectResult::Failure(L::failure(failure.duplicate()))
+                        }
+                        _ => EffectResult::Done(Multiple),
+                    };
+                }
+
+                lock(&self.shared.wakers.0)[index] = Some(cx.waker().clone());
+                if !L::DROPS && state.buffer.len() >= state.capacity {
+                    return EffectResult::Pending(Async);
+                }
+            }
+
+            // one consumer polls the stream at a time, and wakes the others once it has an item.
+            // The state is not locked meanwhile, but the item is pushed before the stream is
+            // released, so the items stay in order
+            let mut source = match self.shared.source.try_lock() {
+                Ok(source) => source,
+                Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                Err(TryLockError::WouldBlock) => return EffectResult::Pending(Async),
+            };
+            let Some(effect) = source.as_mut() else {
+                // the stream completed since the state was checked
+                continue;
+            };
+            let waker = Waker::from(self.shared.wakers.clone());
+            let polled = effect
+                .as_mut()
+                .poll_effect(&mut Context::from_waker(&waker));
+
+            let mut state = lock(&self.shared.state);
+            match polled {
+                EffectResult::Item(item) => {
+                    lock(&self.shared.wakers.0)[index] = None;
+                    state.buffer.push_back(item);
+                    if state.buffer.len() > state.capacity {
+                        // make room by dropping the oldest item, which lagging consumers will miss
+                        state.buffer.pop_front();
+                        state.head += 1;
+                    }
+                    // the other consumers can read the new item
+                    self.shared.wakers.wake_by_ref();
+                }
+                EffectResult::Failure(failure) => {
+                    *source = None;
+                    state.done = true;
+                    state.failure = Some(failure);
+                    self.shared.wakers.wake_by_ref();
+                }
+                EffectResult::Done(_) => {
+                    *source = None;
+                    state.done = true;
+                    self.shared.wakers.wake_by_ref();
+                }
+                EffectResult::Pending(_) => return EffectResult::Pending(Async),
+            }
+        }
+    }
+}
+
+impl<E: Effective, L> Drop for Shared<E, L> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared.state);
+        state.cursors[self.index] = None;
+        lock(&self.shared.wakers.0)[self.index] = None;
+        state.prune();
+        drop(state);
+        // the other consumers might have been waiting for this one to catch up
+        self.shared.wakers.wake_by_ref();
+    }
+}
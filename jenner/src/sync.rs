@@ -6,49 +6,90 @@ use std::{
     pin::Pin,
 };
 
+use crate::EffectiveReturn;
+
 pin_project_lite::pin_project!(
     #[doc(hidden)]
-    pub struct SyncGeneratorImpl<G> {
+    #[derive(Clone)]
+    pub struct SyncGeneratorImpl<G, R> {
         #[pin]
         generator: G,
+        output: Option<R>,
     }
 );
 
 pin_project_lite::pin_project!(
     #[doc(hidden)]
-    pub struct SyncFallibleGeneratorImpl<G> {
+    #[derive(Clone)]
+    pub struct SyncFallibleGeneratorImpl<G, R> {
         #[pin]
         generator: G,
+        output: Option<R>,
     }
 );
 
-impl<G> SyncGeneratorImpl<G> {
+impl<G, R> SyncGeneratorImpl<G, R> {
+    /// Creates the effect without hiding its type, so that it can be cloned
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            output: None,
+        }
+    }
+
     #[doc(hidden)]
     pub fn create<Y>(
         generator: G,
-    ) -> impl Effective<Item = Y, Produces = Multiple, Failure = Infallible, Async = Blocking>
+    ) -> impl EffectiveReturn<
+        Output = R,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Infallible,
+        Async = Blocking,
+    >
     where
-        G: Generator<(), Yield = Y, Return = ()>,
+        G: Generator<(), Yield = Y, Return = R>,
     {
-        Self { generator }
+        Self {
+            generator,
+            output: None,
+        }
     }
 }
 
-impl<G> SyncFallibleGeneratorImpl<G> {
+impl<G, R> SyncFallibleGeneratorImpl<G, R> {
+    #[doc(hidden)]
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            output: None,
+        }
+    }
+
     #[doc(hidden)]
     pub fn create<Y, E>(
         generator: G,
-    ) -> impl Effective<Item = Y, Produces = Multiple, Failure = Failure<E>, Async = Blocking>
+    ) -> impl EffectiveReturn<
+        Output = R,
+        Item = Y,
+        Produces = Multiple,
+        Failure = Failure<E>,
+        Async = Blocking,
+    >
     where
-        G: Generator<(), Yield = Y, Return = Result<(), E>>,
+        G: Generator<(), Yield = Y, Return = Result<R, E>>,
     {
-        Self { generator }
+        Self {
+            generator,
+            output: None,
+        }
     }
 }
 
-impl<G> Effective for SyncGeneratorImpl<G>
+impl<G, R> Effective for SyncGeneratorImpl<G, R>
 where
-    G: Generator<(), Return = ()>,
+    G: Generator<(), Return = R>,
 {
     type Item = G::Yield;
     type Failure = Infallible;
@@ -59,16 +100,31 @@ where
         self: Pin<&mut Self>,
         _cx: &mut std::task::Context<'_>,
     ) -> EffectiveResult<Self> {
-        match self.project().generator.resume(()) {
+        let this = self.project();
+        match this.generator.resume(()) {
             GeneratorState::Yielded(x) => EffectResult::Item(x),
-            GeneratorState::Complete(()) => EffectResult::Done(Multiple),
+            GeneratorState::Complete(r) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
         }
     }
 }
 
-impl<G, E> Effective for SyncFallibleGeneratorImpl<G>
+impl<G, R> EffectiveReturn for SyncGeneratorImpl<G, R>
 where
-    G: Generator<(), Return = Result<(), E>>,
+    G: Generator<(), Return = R>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
+
+impl<G, R, E> Effective for SyncFallibleGeneratorImpl<G, R>
+where
+    G: Generator<(), Return = Result<R, E>>,
 {
     type Item = G::Yield;
     type Failure = Failure<E>;
@@ -79,10 +135,25 @@ where
         self: Pin<&mut Self>,
         _cx: &mut std::task::Context<'_>,
     ) -> EffectiveResult<Self> {
-        match self.project().generator.resume(()) {
+        let this = self.project();
+        match this.generator.resume(()) {
             GeneratorState::Yielded(x) => EffectResult::Item(x),
             GeneratorState::Complete(Err(e)) => EffectResult::Failure(Failure(e)),
-            GeneratorState::Complete(Ok(())) => EffectResult::Done(Multiple),
+            GeneratorState::Complete(Ok(r)) => {
+                *this.output = Some(r);
+                EffectResult::Done(Multiple)
+            }
         }
     }
 }
+
+impl<G, R, E> EffectiveReturn for SyncFallibleGeneratorImpl<G, R>
+where
+    G: Generator<(), Return = Result<R, E>>,
+{
+    type Output = R;
+
+    fn take_output(self: Pin<&mut Self>) -> Option<R> {
+        self.project().output.take()
+    }
+}
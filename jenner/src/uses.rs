@@ -0,0 +1,462 @@
+use std::{
+    convert::Infallible,
+    marker::PhantomData,
+    ops::{Generator, GeneratorState},
+    pin::Pin,
+    task::Context,
+};
+
+use effective::{EffectResult, Effective, EffectiveResult, Failure, Multiple, Single};
+
+use crate::asynch::UnsafeContextRef;
+
+/// A request that can be performed by `#[effect(uses(...))]` bodies with `perform!(op)`,
+/// and answered by a [`Handler`].
+///
+/// ```
+/// #![feature(generators)]
+///
+/// use effective::EffectiveExt;
+/// use jenner::{effect, perform, Handler, Operation};
+///
+/// enum Log {
+///     Info(String),
+/// }
+///
+/// struct GetConfig;
+///
+/// impl Operation for Log {
+///     type Output = ();
+/// }
+///
+/// impl Operation for GetConfig {
+///     type Output = u32;
+/// }
+///
+/// #[effect(uses(Log, GetConfig))]
+/// async fn app() -> u32 {
+///     let retries = perform!(GetConfig);
+///     perform!(Log::Info(format!("retrying {retries} times")));
+///     retries
+/// }
+///
+/// /// Collects the logs, so they can be checked in tests
+/// struct MockLog(Vec<String>);
+///
+/// impl Handler for &mut MockLog {
+///     type Op = Log;
+///     fn handle(&mut self, Log::Info(msg): Log) {
+///         self.0.push(msg);
+///     }
+/// }
+///
+/// struct MockConfig;
+///
+/// impl Handler for MockConfig {
+///     type Op = GetConfig;
+///     fn handle(&mut self, _: GetConfig) -> u32 {
+///         3
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut logs = MockLog(vec![]);
+///     let retries = jenner::handle(app(), (&mut logs, MockConfig)).shim().await;
+///     assert_eq!(retries, 3);
+///     assert_eq!(logs.0, vec!["retrying 3 times"]);
+/// }
+/// ```
+pub trait Operation {
+    /// The response to this request
+    type Output;
+}
+
+/// Answers the [`Operation`]s performed by an effect. See [`handle`]
+pub trait Handler {
+    type Op: Operation;
+    fn handle(&mut self, op: Self::Op) -> <Self::Op as Operation>::Output;
+}
+
+/// The operations of an effect, as a list `(A, (B, ()))`.
+/// `#[effect(uses(A, B))]` builds the list from the listed operations
+pub trait Operations {
+    /// One of the operations in the list
+    type Request;
+    /// The output of one of the operations in the list
+    type Response;
+}
+
+impl Operations for () {
+    type Request = Infallible;
+    type Response = Infallible;
+}
+
+impl<Op: Operation, Rest: Operations> Operations for (Op, Rest) {
+    type Request = Choice<Op, Rest::Request>;
+    type Response = Choice<Op::Output, Rest::Response>;
+}
+
+/// A value for either the head or the rest of an [`Operations`] list
+#[doc(hidden)]
+pub enum Choice<H, T> {
+    Here(H),
+    There(T),
+}
+
+/// A set of [`Handler`]s that answers every operation in `Ops`, implemented for tuples of handlers.
+///
+/// `I` is inferred, and records which handler answers each operation.
+pub trait Handlers<Ops: Operations, I> {
+    /// Answers the request with the handler of its operation
+    fn handle(&mut self, request: Ops::Request) -> Ops::Response;
+}
+
+impl<H> Handlers<(), ()> for H {
+    fn handle(&mut self, request: Infallible) -> Infallible {
+        match request {}
+    }
+}
+
+impl<H, Op, Rest, I, Is> Handlers<(Op, Rest), (I, Is)> for H
+where
+    H: Provides<Op, I> + Handlers<Rest, Is>,
+    Op: Operation,
+    Rest: Operations,
+{
+    fn handle(&mut self, request: Choice<Op, Rest::Request>) -> Choice<Op::Output, Rest::Response> {
+        match request {
+            Choice::Here(op) => Choice::Here(self.provide(op)),
+            Choice::There(rest) => Choice::There(Handlers::<Rest, Is>::handle(self, rest)),
+        }
+    }
+}
+
+/// An effect created with `#[effect(uses(...))]`, that performs the [`Operation`]s in `Ops`.
+/// It can be turned into an [`Effective`] by providing the [`Handlers`] with [`handle`]
+pub trait Uses {
+    /// The operations that this effect can perform
+    type Ops: Operations;
+    type Item;
+    type Failure;
+    type Produces;
+    type Async;
+
+    fn poll_uses<H: Handlers<Self::Ops, I>, I>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        handlers: &mut H,
+    ) -> EffectResult<Self::Item, Self::Failure, Self::Produces, Self::Async>;
+}
+
+/// Answers each [`Operation`] performed by the effect using the given handlers.
+///
+/// The handlers are a tuple, eg `handle(app(), (LogHandler, ConfigHandler))`.
+/// Every operation of the effect needs exactly one handler, which is checked at compile time:
+///
+/// ```compile_fail
+/// #![feature(generators)]
+///
+/// use jenner::{effect, perform, Operation};
+///
+/// struct GetConfig;
+///
+/// impl Operation for GetConfig {
+///     type Output = u32;
+/// }
+///
+/// #[effect(uses(GetConfig))]
+/// fn app() -> u32 {
+///     perform!(GetConfig)
+/// }
+///
+/// // no handler for `GetConfig`
+/// let _ = jenner::handle(app(), ());
+/// ```
+pub fn handle<E: Uses, H: Handlers<E::Ops, I>, I>(effect: E, handlers: H) -> Handled<E, H, I> {
+    Handled {
+        effect,
+        handlers,
+        _index: PhantomData,
+    }
+}
+
+pin_project_lite::pin_project!(
+    /// An effect with its operations handled. Created with [`handle`]
+    pub struct Handled<E, H, I> {
+        #[pin]
+        effect: E,
+        handlers: H,
+        _index: PhantomData<fn() -> I>,
+    }
+);
+
+impl<E: Uses, H: Handlers<E::Ops, I>, I> Effective for Handled<E, H, I> {
+    type Item = E::Item;
+    type Failure = E::Failure;
+    type Produces = E::Produces;
+    type Async = E::Async;
+
+    fn poll_effect(self: Pin<&mut Self>, cx: &mut Context<'_>) -> EffectiveResult<Self> {
+        let this = self.project();
+        this.effect.poll_uses(cx, this.handlers)
+    }
+}
+
+/// The handler for `Op` in the tuple `Self`, at position `I`
+#[doc(hidden)]
+pub trait Provides<Op: Operation, I> {
+    fn provide(&mut self, op: Op) -> Op::Output;
+}
+
+macro_rules! provides {
+    ($($h:ident),*) => {
+        provides!(@ [$($h),*] [$($h),*] [0 1 2 3 4 5 6 7]);
+    };
+    (@ [$($h:ident),*] [] [$($i:tt)*]) => {};
+    (@ [$($h:ident),*] [$handler:ident $(, $rest:ident)*] [$i:tt $($is:tt)*]) => {
+        impl<$($h: Handler),*> Provides<$handler::Op, Index<$i>> for ($($h,)*) {
+            fn provide(&mut self, op: $handler::Op) -> <$handler::Op as Operation>::Output {
+                self.$i.handle(op)
+            }
+        }
+        provides!(@ [$($h),*] [$($rest),*] [$($is)*]);
+    };
+}
+
+provides!(A);
+provides!(A, B);
+provides!(A, B, C);
+provides!(A, B, C, D);
+provides!(A, B, C, D, E);
+provides!(A, B, C, D, E, F);
+provides!(A, B, C, D, E, F, G);
+provides!(A, B, C, D, E, F, G, H);
+
+#[doc(hidden)]
+pub struct Index<const I: usize>;
+
+/// The position `I` of `Op` in the list `Self`, so that `perform!` only accepts the operations in `uses(...)`
+#[doc(hidden)]
+pub trait Contains<Op: Operation, I>: Operations {
+    fn request(op: Op) -> Self::Request;
+    fn response(response: Self::Response) -> Op::Output;
+}
+
+#[doc(hidden)]
+pub struct Here;
+
+#[doc(hidden)]
+pub struct There<I>(PhantomData<I>);
+
+impl<Op: Operation, Rest: Operations> Contains<Op, Here> for (Op, Rest) {
+    fn request(op: Op) -> Self::Request {
+        Choice::Here(op)
+    }
+    fn response(response: Self::Response) -> Op::Output {
+        match response {
+            Choice::Here(output) => output,
+            Choice::There(_) => {
+                unreachable!("handlers respond to the operation that was requested")
+            }
+        }
+    }
+}
+
+impl<Op: Operation, H: Operation, Rest: Contains<Op, I>, I> Contains<Op, There<I>> for (H, Rest) {
+    fn request(op: Op) -> Self::Request {
+        Choice::There(Rest::request(op))
+    }
+    fn response(response: Self::Response) -> Op::Output {
+        match response {
+            Choice::Here(_) => unreachable!("handlers respond to the operation that was requested"),
+            Choice::There(rest) => Rest::response(rest),
+        }
+    }
+}
+
+/// Creates the request for `perform!(op)`, and the typed response to it
+#[doc(hidden)]
+pub fn request<Ops, Op, I>(op: Op) -> (Ops::Request, PendingResponse<Ops, Op, I>)
+where
+    Ops: Contains<Op, I>,
+    Op: Operation,
+{
+    (Ops::request(op), PendingResponse(PhantomData))
+}
+
+#[doc(hidden)]
+pub struct PendingResponse<Ops, Op, I>(PhantomData<fn() -> (Ops, Op, I)>);
+
+impl<Ops: Contains<Op, I>, Op: Operation, I> PendingResponse<Ops, Op, I> {
+    #[doc(hidden)]
+    pub fn resumed(
+        self,
+        resume: PerformContext<Ops::Response>,
+    ) -> (Option<UnsafeContextRef>, Op::Output) {
+        let response = resume
+            .response
+            .expect("generator was resumed without a response");
+        (resume.cx, Ops::response(response))
+    }
+}
+
+/// What an `#[effect(uses(...))]` generator suspends with
+#[doc(hidden)]
+pub enum Suspend<Y, A, R> {
+    Item(Y),
+    Pending(A),
+    Perform(R),
+}
+
+/// Resume argument of `#[effect(uses(...))]` generators
+#[doc(hidden)]
+pub struct PerformContext<R> {
+    cx: Option<UnsafeContextRef>,
+    response: Option<R>,
+}
+
+impl<R> PerformContext<R> {
+    #[doc(hidden)]
+    pub fn into_context(self) -> UnsafeContextRef {
+        self.cx.expect("blocking generators have no context")
+    }
+}
+
+/// How the return value of a generator completes the effect
+#[doc(hidden)]
+pub trait Mode<Y, R> {
+    type Item;
+    type Failure;
+    type Produces;
+    fn item(y: Y) -> Self::Item;
+    fn complete<A>(r: R) -> EffectResult<Self::Item, Self::Failure, Self::Produces, A>;
+}
+
+#[doc(hidden)]
+pub struct Stream;
+#[doc(hidden)]
+pub struct FallibleStream;
+#[doc(hidden)]
+pub struct Task;
+#[doc(hidden)]
+pub struct FallibleTask;
+
+impl<Y> Mode<Y, ()> for Stream {
+    type Item = Y;
+    type Failure = Infallible;
+    type Produces = Multiple;
+    fn item(y: Y) -> Y {
+        y
+    }
+    fn complete<A>((): ()) -> EffectResult<Y, Infallible, Multiple, A> {
+        EffectResult::Done(Multiple)
+    }
+}
+
+impl<Y, E> Mode<Y, Result<(), E>> for FallibleStream {
+    type Item = Y;
+    type Failure = Failure<E>;
+    type Produces = Multiple;
+    fn item(y: Y) -> Y {
+        y
+    }
+    fn complete<A>(r: Result<(), E>) -> EffectResult<Y, Failure<E>, Multiple, A> {
+        match r {
+            Ok(()) => EffectResult::Done(Multiple),
+            Err(e) => EffectResult::Failure(Failure(e)),
+        }
+    }
+}
+
+impl<T> Mode<Infallible, T> for Task {
+    type Item = T;
+    type Failure = Infallible;
+    type Produces = Single;
+    fn item(y: Infallible) -> T {
+        match y {}
+    }
+    fn complete<A>(r: T) -> EffectResult<T, Infallible, Single, A> {
+        EffectResult::Item(r)
+    }
+}
+
+impl<T, E> Mode<Infallible, Result<T, E>> for FallibleTask {
+    type Item = T;
+    type Failure = Failure<E>;
+    type Produces = Single;
+    fn item(y: Infallible) -> T {
+        match y {}
+    }
+    fn complete<A>(r: Result<T, E>) -> EffectResult<T, Failure<E>, Single, A> {
+        match r {
+            Ok(x) => EffectResult::Item(x),
+            Err(e) => EffectResult::Failure(Failure(e)),
+        }
+    }
+}
+
+pin_project_lite::pin_project!(
+    #[doc(hidden)]
+    pub struct UsesImpl<G, Ops, M, A> {
+        #[pin]
+        generator: G,
+        _marker: PhantomData<fn() -> (Ops, M, A)>,
+    }
+);
+
+impl<G, Ops: Operations, M, A> UsesImpl<G, Ops, M, A> {
+    #[doc(hidden)]
+    pub fn create<Y, R>(
+        generator: G,
+    ) -> impl Uses<Ops = Ops, Item = M::Item, Failure = M::Failure, Produces = M::Produces, Async = A>
+    where
+        G: Generator<
+            PerformContext<Ops::Response>,
+            Yield = Suspend<Y, A, Ops::Request>,
+            Return = R,
+        >,
+        M: Mode<Y, R>,
+    {
+        Self {
+            generator,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<G, Ops, M, A, Y, R> Uses for UsesImpl<G, Ops, M, A>
+where
+    Ops: Operations,
+    G: Generator<PerformContext<Ops::Response>, Yield = Suspend<Y, A, Ops::Request>, Return = R>,
+    M: Mode<Y, R>,
+{
+    type Ops = Ops;
+    type Item = M::Item;
+    type Failure = M::Failure;
+    type Produces = M::Produces;
+    type Async = A;
+
+    fn poll_uses<H: Handlers<Ops, I>, I>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        handlers: &mut H,
+    ) -> EffectResult<Self::Item, Self::Failure, Self::Produces, Self::Async> {
+        let mut generator = self.project().generator;
+        let mut response = None;
+        loop {
+            let resume = PerformContext {
+                cx: Some(cx.into()),
+                response: response.take(),
+            };
+            match generator.as_mut().resume(resume) {
+                GeneratorState::Yielded(Suspend::Item(y)) => return EffectResult::Item(M::item(y)),
+                GeneratorState::Yielded(Suspend::Pending(a)) => return EffectResult::Pending(a),
+                GeneratorState::Yielded(Suspend::Perform(request)) => {
+                    response = Some(handlers.handle(request));
+                }
+                GeneratorState::Complete(r) => return M::complete(r),
+            }
+        }
+    }
+}
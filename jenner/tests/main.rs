@@ -1,10 +1,16 @@
-#![feature(generators)]
+#![feature(
+    async_iterator,
+    generators,
+    generator_clone,
+    generator_trait,
+    stmt_expr_attributes
+)]
 
-use effective::{wrappers::future, Async, Effective, EffectiveExt, Multiple};
-use jenner::effect;
+use effective::{wrappers::future, Async, Effective, EffectiveExt, Failure, Multiple};
+use jenner::{effect, Cancellable, EffectiveResume, EffectiveReturn};
 use std::{
     convert::Infallible,
-    pin::pin,
+    pin::{pin, Pin},
     time::{Duration, Instant},
 };
 
@@ -94,3 +100,1561 @@ async fn countdown1() -> u32 {
         yield i;
     }
 }
+
+#[tokio::test]
+async fn into_future() {
+    let v = collect(countdown_futures()).shim().await;
+    assert_eq!(v, vec![3, 2, 1]);
+}
+
+#[effect(yields)]
+async fn countdown_futures() -> u32 {
+    for i in (1..=3).rev() {
+        // futures and other `IntoFuture` values can be awaited without wrapping them
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        yield Ready(i).await;
+    }
+}
+
+/// Resolves to its value, as an [`IntoFuture`](std::future::IntoFuture)
+struct Ready(u32);
+
+impl std::future::IntoFuture for Ready {
+    type Output = u32;
+    type IntoFuture = std::future::Ready<u32>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        std::future::ready(self.0)
+    }
+}
+
+#[tokio::test]
+async fn pull() {
+    let v = interleave(countdown(), double(countdown1())).shim().await;
+    assert_eq!(v, vec![5, 10, 4, 8, 3, 6, 2, 4, 1, 2, 0, 0]);
+}
+
+#[effect]
+async fn interleave(
+    a: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+    b: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+) -> Vec<u32> {
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+    let mut v = vec![];
+    while let Some(x) = jenner::next(&mut a).await {
+        v.push(x);
+        if let Some(y) = jenner::next(&mut b).await {
+            v.push(y);
+        }
+    }
+    v
+}
+
+#[test]
+fn pull_sync() {
+    let v: Vec<_> = pin!(pairs()).shim().take(3).collect();
+    assert_eq!(v, vec![(0, 1), (1, 2), (3, 5)]);
+}
+
+#[effect(yields)]
+fn pairs() -> (usize, usize) {
+    let mut fib = pin!(fibonacii());
+    while let Some(a) = jenner::next(&mut fib).await {
+        let Some(b) = jenner::next(&mut fib).await else { break };
+        yield (a, b);
+    }
+}
+
+#[tokio::test]
+async fn for_conversions() {
+    let v = sum_all(vec![1, 2], futures::stream::iter([3, 4]), Ticks(2))
+        .shim()
+        .await;
+    assert_eq!(v, 1 + 2 + 3 + 4 + 5 + 4 + 3 + 2 + 1 + 2 + 1);
+}
+
+#[effect]
+async fn sum_all(
+    iter: Vec<u32>,
+    stream: impl futures::Stream<Item = u32>,
+    async_iter: Ticks,
+) -> u32 {
+    let mut total = 0;
+    #[effect(async)]
+    for i in iter {
+        total += i;
+    }
+    #[effect(async)]
+    for i in stream {
+        total += i;
+    }
+    #[effect(async)]
+    for i in countdown1() {
+        total += i;
+    }
+    #[effect(async)]
+    for i in async_iter {
+        total += i;
+    }
+    total
+}
+
+/// Counts down from `n`, as an [`AsyncIterator`](std::async_iter::AsyncIterator)
+struct Ticks(u32);
+
+impl std::async_iter::AsyncIterator for Ticks {
+    type Item = u32;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<u32>> {
+        let n = self.0;
+        self.0 = n.saturating_sub(1);
+        std::task::Poll::Ready((n > 0).then_some(n))
+    }
+}
+
+#[tokio::test]
+async fn lifting() {
+    let v = collect_checked(checked(countdown())).shim().await;
+    assert_eq!(v, (vec![1, 5, 4, 3], Some("too small")));
+}
+
+#[effect]
+fn offset() -> u32 {
+    1
+}
+
+#[effect(fallible)]
+async fn check(x: u32) -> Result<u32, &'static str> {
+    if x < 3 {
+        return Err("too small");
+    }
+    x
+}
+
+#[effect(yields, fallible)]
+async fn checked(
+    input: impl Effective<Item = u32, Failure = Infallible, Produces = Multiple, Async = Async>,
+) -> Result<u32, &'static str> {
+    // blocking single effects can be delegated to
+    #[effect(async)]
+    for i in offset() {
+        yield i;
+    }
+    // infallible streams can be used in fallible bodies
+    #[effect(async)]
+    for i in input {
+        // blocking effects can be awaited in async bodies
+        let _ = offset().await;
+        // failures are propagated from fallible effects
+        yield check(i).await;
+    }
+}
+
+#[effect]
+async fn collect_checked(
+    input: impl Effective<Item = u32, Failure = Failure<&'static str>, Produces = Multiple, Async = Async>,
+) -> (Vec<u32>, Option<&'static str>) {
+    let mut input = pin!(input);
+    let mut v = vec![];
+    loop {
+        match jenner::try_next(&mut input).await {
+            Ok(Some(x)) => v.push(x),
+            Ok(None) => break (v, None),
+            Err(e) => break (v, Some(e)),
+        }
+    }
+}
+
+#[test]
+fn arguments() {
+    let v: Vec<_> = pin!(repeat(7, 3)).shim().collect();
+    assert_eq!(v, vec![7, 7, 7]);
+}
+
+#[effect(yields)]
+fn repeat(x: u32, n: usize) -> u32 {
+    // `x` and `n` are only copied, which would otherwise borrow them from the returned call
+    for _ in 0..n {
+        yield x;
+    }
+}
+
+#[tokio::test]
+async fn outputs() {
+    let (v, summary) = collect_summary(evens(6)).shim().await;
+    assert_eq!(v, vec![0, 2, 4]);
+    assert_eq!(summary, Some(3));
+    assert_eq!(evens(10).into_output().shim().await, 5);
+    assert_eq!(sum_evens(6).shim().await, 12);
+
+    let v: Vec<_> = pin!(words("a b c")).shim().collect();
+    assert_eq!(v, vec!["a", "b", "c"]);
+}
+
+#[effect(yields = u32)]
+async fn evens(n: u32) -> usize {
+    let mut count = 0;
+    for i in (0..n).step_by(2) {
+        yield i;
+        count += 1;
+    }
+    count
+}
+
+#[effect]
+async fn sum_evens(n: u32) -> u32 {
+    let mut sum = 0;
+    for _ in 0..2 {
+        // for-loops that don't initialize a `let` evaluate to `()`
+        if n > 0 {
+            #[effect(async)]
+            for i in evens(n) {
+                sum += i;
+            }
+        }
+    }
+    sum
+}
+
+#[effect(yields = &'a str)]
+fn words<'a>(s: &'a str) -> usize {
+    let mut count = 0;
+    for word in s.split(' ') {
+        yield word;
+        count += 1;
+    }
+    count
+}
+
+#[effect]
+async fn collect_summary(
+    input: impl EffectiveReturn<Item = u32, Output = usize, Failure = Infallible, Async = Async>,
+) -> (Vec<u32>, Option<usize>) {
+    let mut v = vec![];
+    let summary = #[effect(async)]
+    for i in input {
+        v.push(i);
+    };
+    (v, summary)
+}
+
+#[tokio::test]
+async fn resume() {
+    let v = drive_parser(parser()).shim().await;
+    assert_eq!(v, vec![12, 345]);
+
+    let v = collect_checked(drive_echo(echo())).shim().await;
+    assert_eq!(v, (vec![2, 4], Some("thrown")));
+
+    // polled without a value, the `yield` evaluates to `None`
+    let v: Vec<_> = pin!(parser()).shim().collect();
+    assert_eq!(v, vec![None]);
+
+    // a value sent while the generator is pending on an await is kept for its next `yield`
+    let mut echo = pin!(slow_echo());
+    assert_eq!(jenner::next(&mut echo).shim().await, Some(0));
+    let pending = tokio::time::timeout(Duration::ZERO, echo.as_mut().resume_with(1).shim());
+    assert!(pending.await.is_err());
+    assert_eq!(echo.as_mut().resume_with(2).shim().await, Some(1));
+    assert_eq!(jenner::next(&mut echo).shim().await, Some(2));
+}
+
+/// Push parser that accumulates digits, yielding the number when sent a space
+#[effect(yields, resumes(char))]
+fn parser() -> Option<u32> {
+    let mut n = 0;
+    let mut parsed = None;
+    while let Some(c) = yield parsed.take() {
+        if c == ' ' {
+            parsed = Some(n);
+            n = 0;
+        } else {
+            n = n * 10 + c.to_digit(10).unwrap();
+        }
+    }
+}
+
+#[effect]
+async fn drive_parser(
+    parser: impl EffectiveResume<Resume = char, Item = Option<u32>, Failure = Infallible>,
+) -> Vec<u32> {
+    let mut parser = pin!(parser);
+    let mut v = vec![];
+    jenner::next(&mut parser).await;
+    for c in "12 345 ".chars() {
+        if let Some(Some(n)) = parser.as_mut().resume_with(c).await {
+            v.push(n);
+        }
+    }
+    v
+}
+
+#[effect(yields, resumes(u32), fallible)]
+fn echo() -> Result<u32, &'static str> {
+    let mut sent = yield 0;
+    while let Some(x) = sent {
+        sent = yield x * 2;
+    }
+}
+
+#[effect(yields, resumes(u32))]
+async fn slow_echo() -> u32 {
+    let mut sent = yield 0;
+    while let Some(x) = sent {
+        tokio::task::yield_now().await;
+        sent = yield x;
+    }
+}
+
+#[effect(yields, fallible)]
+async fn drive_echo(
+    echo: impl EffectiveResume<
+        Resume = u32,
+        Throw = &'static str,
+        Item = u32,
+        Failure = Failure<&'static str>,
+    >,
+) -> Result<u32, &'static str> {
+    let mut echo = pin!(echo);
+    jenner::try_next(&mut echo).await?;
+    yield echo.as_mut().resume_with(1).await.unwrap();
+    yield echo.as_mut().resume_with(2).await.unwrap();
+    // the error is returned from the suspended `yield`, failing `echo`
+    yield echo.as_mut().throw("thrown").await.unwrap();
+}
+
+#[tokio::test]
+async fn try_operands() {
+    assert_eq!(parse_trimmed(" 12 ").shim().await, Ok(12));
+    assert!(parse_trimmed("x").shim().await.is_err());
+}
+
+#[effect(fallible)]
+async fn parse_trimmed(s: &'static str) -> Result<u32, std::num::ParseIntError> {
+    // the await inside the `?` is lowered with the rest of the body
+    async { s.trim().parse::<u32>() }.await?
+}
+
+#[test]
+fn lending() {
+    let v: Vec<_> = pin!(window_sums(vec![1, 2, 3, 4])).shim().collect();
+    assert_eq!(v, vec![10, 9, 7, 4]);
+}
+
+#[effect(yields, lending)]
+fn suffixes(buf: Vec<u8>) -> &[u8] {
+    for i in 0..buf.len() {
+        yield &buf[i..];
+    }
+}
+
+#[effect(yields)]
+fn window_sums(buf: Vec<u8>) -> u32 {
+    #[effect(lending)]
+    for suffix in suffixes(buf) {
+        yield suffix.iter().map(|&x| x as u32).sum();
+    }
+}
+
+#[tokio::test]
+async fn async_lending() {
+    assert_eq!(
+        pending_window_sums(vec![1, 2, 3, 4]).shim().await,
+        vec![10, 9, 7, 4]
+    );
+
+    for blocking in [false, true] {
+        let seen = std::cell::RefCell::new(vec![]);
+        let sums = checked_window_sums(vec![1, 2, 0, 4], blocking, &seen);
+        assert_eq!(sums.shim().await, Err("zero"));
+        // the failure ended the loop
+        assert_eq!(*seen.borrow(), vec![7, 6]);
+    }
+}
+
+#[effect(yields, lending)]
+async fn pending_suffixes(buf: Vec<u8>) -> &[u8] {
+    for i in 0..buf.len() {
+        future(yield_once()).await;
+        yield &buf[i..];
+    }
+}
+
+#[effect]
+async fn pending_window_sums(buf: Vec<u8>) -> Vec<u32> {
+    let mut sums = vec![];
+    #[effect(async, lending)]
+    for suffix in pending_suffixes(buf) {
+        // the suffix is still lent while the body is suspended
+        future(yield_once()).await;
+        sums.push(suffix.iter().map(|&x| x as u32).sum());
+    }
+    sums
+}
+
+#[effect(yields, lending, fallible)]
+async fn nonzero_suffixes(buf: Vec<u8>) -> Result<&[u8], &'static str> {
+    for i in 0..buf.len() {
+        future(yield_once()).await;
+        let suffix = &buf[i..];
+        if suffix[0] == 0 {
+            return Err("zero");
+        }
+        yield suffix;
+    }
+}
+
+#[effect(yields, lending, fallible)]
+fn checked_suffixes(buf: Vec<u8>) -> Result<&[u8], &'static str> {
+    for i in 0..buf.len() {
+        let suffix = &buf[i..];
+        if suffix[0] == 0 {
+            return Err("zero");
+        }
+        yield suffix;
+    }
+}
+
+#[effect(fallible)]
+async fn checked_window_sums(
+    buf: Vec<u8>,
+    blocking: bool,
+    seen: &std::cell::RefCell<Vec<u32>>,
+) -> Result<(), &'static str> {
+    if blocking {
+        #[effect(lending)]
+        for suffix in checked_suffixes(buf) {
+            seen.borrow_mut().push(suffix.iter().map(|&x| x as u32).sum());
+        }
+    } else {
+        #[effect(async, lending)]
+        for suffix in nonzero_suffixes(buf) {
+            seen.borrow_mut().push(suffix.iter().map(|&x| x as u32).sum());
+        }
+    }
+}
+
+#[test]
+fn clone() {
+    let mut a = countup(4);
+    let head: Vec<_> = Pin::new(&mut a).shim().take(2).collect();
+    // fork the iteration state part way through
+    let b = a.clone();
+    let a: Vec<_> = pin!(a).shim().collect();
+    let b: Vec<_> = pin!(b).shim().collect();
+    assert_eq!(head, vec![0, 1]);
+    assert_eq!(a, vec![2, 3]);
+    assert_eq!(a, b);
+}
+
+#[effect(yields, clone)]
+fn countup(n: u32) -> u32 {
+    for i in 0..n {
+        yield i;
+    }
+}
+
+#[tokio::test]
+async fn movable() {
+    let ticks = ticks(3);
+    // no pinning required
+    fn unpin<T: Unpin>(t: T) -> T {
+        t
+    }
+    let v = collect(unpin(ticks)).shim().await;
+    assert_eq!(v, vec![0, 1, 2]);
+}
+
+#[effect(yields, movable)]
+async fn ticks(n: u32) -> u32 {
+    for i in 0..n {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        yield i;
+    }
+}
+
+#[tokio::test]
+async fn maybe_async() {
+    assert_eq!(total(3).shim().await, 8);
+
+    let v: Vec<_> = pin!(totals()).shim().collect();
+    assert_eq!(v, vec![2, 4, 8]);
+
+    assert_eq!(ready_sum(2).shim().await, 3);
+    let v: Vec<_> = pin!(ready_sums()).shim().collect();
+    assert_eq!(v, vec![3]);
+
+    assert_eq!(sum_pending(4).shim().await, 6);
+    let v: Vec<_> = pin!(pending_sums()).shim().collect();
+    assert_eq!(v, vec![6]);
+}
+
+#[effect(yields, maybe_async)]
+async fn pages(n: u32) -> Vec<u32> {
+    for i in 0..n {
+        yield vec![i; 2];
+    }
+}
+
+#[effect(maybe_async)]
+async fn double_of(x: u32) -> u32 {
+    x * 2
+}
+
+#[effect(maybe_async)]
+async fn total(n: u32) -> u32 {
+    let mut sum = 0;
+    #[effect(maybe_async)]
+    for page in pages(n) {
+        sum += page.iter().sum::<u32>();
+    }
+    let bonus = #[effect(maybe_async)]
+    double_of(1).await;
+    sum + bonus
+}
+
+#[effect(yields)]
+fn totals() -> u32 {
+    for n in 1..=3 {
+        yield total_blocking(n).await;
+    }
+}
+
+#[effect(maybe_async)]
+async fn ready_sum(n: u32) -> u32 {
+    // futures can be awaited without wrapping them in both variants
+    let x = std::future::ready(n).await;
+    x + async { 1 }.await
+}
+
+#[effect(yields)]
+fn ready_sums() -> u32 {
+    yield ready_sum_blocking(2).await;
+}
+
+/// Pending once, waking itself straight away
+async fn yield_once() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if std::mem::replace(&mut yielded, true) {
+            std::task::Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[effect(yields)]
+async fn pending_items(n: u32) -> u32 {
+    for i in 0..n {
+        future(yield_once()).await;
+        yield i;
+    }
+}
+
+#[effect(maybe_async)]
+async fn sum_pending(n: u32) -> u32 {
+    // the blocking variant parks the thread while an async effect is pending
+    future(yield_once()).await;
+    let mut sum = 0;
+    #[effect(async)]
+    for i in pending_items(n) {
+        sum += i;
+    }
+    sum
+}
+
+#[effect(yields)]
+fn pending_sums() -> u32 {
+    yield sum_pending_blocking(4).await;
+}
+
+#[test]
+fn dispatch() {
+    let up: Vec<_> = pin!(numbers(true)).shim().collect();
+    assert_eq!(up, vec![0, 1, 2]);
+    let down: Vec<_> = pin!(numbers(false)).shim().collect();
+    assert_eq!(down, vec![3, 2, 1]);
+    let down: Vec<_> = pin!(numbers_from(3)).shim().collect();
+    assert_eq!(down, vec![3, 2, 1]);
+}
+
+#[effect(yields)]
+fn countdown_from(n: u32) -> u32 {
+    for i in (1..=n).rev() {
+        yield i;
+    }
+}
+
+#[effect(yields, dispatch)]
+fn numbers(up: bool) -> u32 {
+    if up {
+        countup(3)
+    } else {
+        countdown_from(3)
+    }
+}
+
+#[effect(yields, dispatch)]
+fn numbers_from(start: u32) -> u32 {
+    match start {
+        0 => countup(3),
+        3 => countdown_from(3),
+        // diverging macros are not wrapped, however they are named
+        _ => std::unreachable!("unsupported start {start}"),
+    }
+}
+
+#[tokio::test]
+async fn dispatch_lifting() {
+    assert_eq!(lookup(0).shim().await, Ok(1));
+    assert_eq!(lookup(5).shim().await, Ok(5));
+    assert_eq!(lookup(1).shim().await, Err("too small"));
+
+    let one: Vec<_> = pin!(one_or_many(false)).shim().collect();
+    assert_eq!(one, vec![1]);
+    let many: Vec<_> = pin!(one_or_many(true)).shim().collect();
+    assert_eq!(many, vec![0, 1, 2]);
+}
+
+#[effect(fallible, dispatch)]
+async fn lookup(id: u32) -> Result<u32, &'static str> {
+    if id == 0 {
+        // blocking and infallible, lifted into the signature like an await
+        offset()
+    } else {
+        check(id)
+    }
+}
+
+#[effect(yields, dispatch)]
+fn one_or_many(many: bool) -> u32 {
+    if many {
+        countup(3)
+    } else {
+        // a single effect is a stream of one item
+        offset()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Frame {
+    Data(u32),
+    Ctrl(&'static str),
+}
+
+#[tokio::test]
+async fn multi_output() {
+    let frames = vec![
+        Frame::Ctrl("open"),
+        Frame::Data(1),
+        Frame::Data(2),
+        Frame::Ctrl("close"),
+    ];
+    let DemuxFramesOutputs { data, ctrl } = demux_frames(frames);
+    // reading ahead on `ctrl` buffers the data frames
+    assert_eq!(collect(ctrl).shim().await, vec!["open", "close"]);
+    assert_eq!(collect(data).shim().await, vec![1, 2]);
+}
+
+#[effect(yields(data: u32, ctrl: &'static str))]
+async fn demux_frames(frames: Vec<Frame>) {
+    for frame in frames {
+        match frame {
+            Frame::Data(x) => yield data(x),
+            Frame::Ctrl(c) => yield ctrl(c),
+        }
+    }
+}
+
+#[tokio::test]
+async fn multi_output_failure() {
+    let PartitionCheckedOutputs { small, large } = partition_checked(vec![1, 20, 0, 3]);
+    let mut small = pin!(small);
+    let mut large = pin!(large);
+    assert_eq!(jenner::try_next(&mut small).shim().await, Ok(Some(1)));
+    assert_eq!(jenner::try_next(&mut small).shim().await, Err("zero".to_owned()));
+    // every output reports the failure, after the items buffered for it
+    assert_eq!(jenner::try_next(&mut large).shim().await, Ok(Some(20)));
+    assert_eq!(jenner::try_next(&mut large).shim().await, Err("zero".to_owned()));
+}
+
+#[effect(yields(small: u32, large: u32), fallible)]
+async fn partition_checked(xs: Vec<u32>) -> Result<(), String> {
+    for x in xs {
+        if x == 0 {
+            return Err("zero".to_owned());
+        }
+        if x < 10 {
+            yield small(x);
+        } else {
+            yield large(x);
+        }
+    }
+}
+
+#[tokio::test]
+async fn recoverable() {
+    let lines = vec!["1", "x", "2", "EOF", "3"];
+    let (sum, errors) = sum_records(parse_records(lines)).shim().await.unwrap_err();
+    assert_eq!(sum, 3);
+    assert_eq!(errors, vec!["bad record \"x\"".to_owned()]);
+}
+
+#[effect(yields, fallible(recoverable))]
+async fn parse_records(lines: Vec<&'static str>) -> Result<u32, String> {
+    for line in lines {
+        if line == "EOF" {
+            return Err("unexpected EOF".to_owned());
+        }
+        match line.parse() {
+            Ok(x) => yield x,
+            Err(_) => jenner::yield_err!(format!("bad record {line:?}")),
+        }
+    }
+}
+
+/// Sums the records, collecting the recoverable errors until the terminal failure
+#[effect]
+async fn sum_records(
+    records: impl Effective<
+        Item = Result<u32, String>,
+        Failure = Failure<String>,
+        Produces = Multiple,
+        Async = Async,
+    >,
+) -> Result<u32, (u32, Vec<String>)> {
+    let mut sum = 0;
+    let mut errors = vec![];
+    let mut records = pin!(records);
+    loop {
+        match jenner::try_next(&mut records).await {
+            Ok(Some(Ok(x))) => sum += x,
+            Ok(Some(Err(e))) => errors.push(e),
+            Ok(None) => break Ok(sum),
+            Err(_) => break Err((sum, errors)),
+        }
+    }
+}
+
+#[test]
+fn uses() {
+    let mut audit = Audit(vec![]);
+    let effect = pin!(jenner::handle(
+        scaled_readings(),
+        (&mut audit, FixedScale(10)),
+    ));
+    let readings: Vec<_> = effect.shim().collect();
+    assert_eq!(readings, vec![10, 20, 30]);
+    assert_eq!(audit.0, vec!["scaled 3 readings by 10"]);
+}
+
+#[tokio::test]
+async fn uses_any_order() {
+    let mut audit = Audit(vec![]);
+    let total = jenner::handle(checked_total(&[1, 2]), (FixedScale(3), &mut audit));
+    assert_eq!(total.shim().await, Ok(9));
+    assert_eq!(audit.0, vec!["total 9"]);
+}
+
+struct Record(String);
+
+struct GetScale;
+
+impl jenner::Operation for Record {
+    type Output = ();
+}
+
+impl jenner::Operation for GetScale {
+    type Output = u32;
+}
+
+#[effect(yields, uses(Record, GetScale))]
+fn scaled_readings() -> u32 {
+    let scale = jenner::perform!(GetScale);
+    for reading in 1..=3 {
+        yield reading * scale;
+    }
+    jenner::perform!(Record(format!("scaled 3 readings by {scale}")));
+}
+
+#[effect(fallible, uses(GetScale, Record))]
+async fn checked_total(xs: &[u32]) -> Result<u32, String> {
+    let scale = jenner::perform!(GetScale);
+    let total = xs.iter().map(|x| x * scale).sum();
+    jenner::perform!(Record(format!("total {total}")));
+    total
+}
+
+struct Audit(Vec<String>);
+
+impl jenner::Handler for &mut Audit {
+    type Op = Record;
+    fn handle(&mut self, Record(entry): Record) {
+        self.0.push(entry);
+    }
+}
+
+struct FixedScale(u32);
+
+impl jenner::Handler for FixedScale {
+    type Op = GetScale;
+    fn handle(&mut self, _: GetScale) -> u32 {
+        self.0
+    }
+}
+
+// paused, so the ticks before the timeout don't depend on the load of the machine
+#[tokio::test(start_paused = true)]
+async fn cancellable() {
+    let log = std::sync::Mutex::new(vec![]);
+    let mut conn = pin!(connection(&log));
+    let received = tokio::time::timeout(Duration::from_millis(250), collect(conn.as_mut()).shim());
+    assert!(received.await.is_err());
+
+    let deadline = future(tokio::time::sleep(Duration::from_secs(1)));
+    assert_eq!(conn.cancel(deadline).shim().await, Ok(()));
+    assert_eq!(*log.lock().unwrap(), vec!["flushed 2"]);
+
+    // cleanup that outlives its deadline is abandoned
+    let mut stuck = pin!(stuck());
+    let _ = tokio::time::timeout(Duration::from_millis(10), stuck.as_mut().shim()).await;
+    let deadline = future(tokio::time::sleep(Duration::from_millis(10)));
+    assert_eq!(
+        stuck.cancel(deadline).shim().await,
+        Err(jenner::DeadlineElapsed)
+    );
+}
+
+#[effect(yields, cancellable)]
+async fn connection(log: &std::sync::Mutex<Vec<String>>) -> u32 {
+    let mut buffered = 0;
+    loop {
+        let tick = tokio::time::sleep(Duration::from_millis(100));
+        let ticked = #[effect(cancellable)]
+        tick.await;
+        if let Err(jenner::Cancelled) = ticked {
+            // the flush can still await, as cancellation is only delivered once
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            log.lock().unwrap().push(format!("flushed {buffered}"));
+            break;
+        }
+        buffered += 1;
+        yield buffered;
+    }
+}
+
+#[effect(cancellable)]
+async fn stuck() {
+    jenner::on_cancel! {
+        std::future::pending::<()>().await;
+    }
+    std::future::pending::<()>().await;
+}
+
+#[tokio::test]
+async fn nested_cleanup() {
+    let log = std::sync::Mutex::new(vec![]);
+    let mut steps = pin!(steps(&log));
+    assert_eq!(jenner::next(&mut steps).shim().await, Some(1));
+    let deadline = future(tokio::time::sleep(Duration::from_secs(1)));
+    assert_eq!(steps.cancel(deadline).shim().await, Ok(()));
+    assert_eq!(*log.lock().unwrap(), vec!["inner", "outer"]);
+
+    let log = std::sync::Mutex::new(vec![]);
+    assert_eq!(collect(batches(&log)).shim().await, vec![1, 3]);
+    assert!(log.lock().unwrap().is_empty());
+    let mut batches = pin!(batches(&log));
+    assert_eq!(jenner::next(&mut batches).shim().await, Some(1));
+    let deadline = future(tokio::time::sleep(Duration::from_secs(1)));
+    assert_eq!(batches.cancel(deadline).shim().await, Ok(()));
+    assert_eq!(*log.lock().unwrap(), vec!["batch 1"]);
+}
+
+#[effect(yields, cancellable)]
+async fn steps(log: &std::sync::Mutex<Vec<&'static str>>) -> u32 {
+    jenner::on_cancel! {
+        log.lock().unwrap().push("outer");
+    }
+    {
+        jenner::on_cancel! {
+            log.lock().unwrap().push("inner");
+        }
+        // the cleanup does not see bindings declared after it
+        let log = ();
+        yield 1;
+        let () = log;
+    }
+    yield 2;
+}
+
+#[effect(yields, cancellable)]
+async fn batches(log: &std::sync::Mutex<Vec<String>>) -> u32 {
+    for i in 1.. {
+        jenner::on_cancel! {
+            log.lock().unwrap().push(format!("batch {i}"));
+        }
+        // the loop is still exited after the `on_cancel!`
+        if i == 2 {
+            continue;
+        }
+        if i > 3 {
+            break;
+        }
+        yield i;
+    }
+}
+
+#[tokio::test]
+async fn captures() {
+    // each of these borrows its arguments, so the effects they return must capture the lifetimes
+    let words = ["a", "bb", "ccc", "dddd"];
+    let v: Vec<_> = pin!(longer_than(&words, &2)).shim().collect();
+    assert_eq!(v, ["ccc", "dddd"]);
+
+    let scaler = Scaler(3);
+    let v: Vec<_> = pin!(scaler.scale(&[1, 2, 3])).shim().collect();
+    assert_eq!(v, [3, 6, 9]);
+
+    assert_eq!(total_len(&words, "e").shim().await, 11);
+
+    let v: Vec<_> = pin!(longer_counts(&words)).shim().collect();
+    assert_eq!(v, [4, 3, 2, 1]);
+}
+
+#[effect(yields)]
+fn longer_than(words: &[&'static str], len: &usize) -> &'static str {
+    for word in words {
+        if word.len() > *len {
+            yield *word;
+        }
+    }
+}
+
+struct Scaler(u32);
+
+impl Scaler {
+    #[effect(yields)]
+    fn scale<'a>(&self, xs: &'a [u32]) -> u32 {
+        for x in xs {
+            yield x * self.0;
+        }
+    }
+}
+
+#[effect]
+async fn total_len(words: &[&str], extra: &'_ str) -> usize {
+    tokio::task::yield_now().await;
+    words.iter().map(|w| w.len()).sum::<usize>() + extra.len()
+}
+
+#[effect]
+fn count_longer(words: &[&str], len: &usize) -> usize {
+    words.iter().filter(|w| w.len() > *len).count()
+}
+
+#[effect(yields)]
+fn longer_counts(words: &[&'static str]) -> usize {
+    for len in 0..4 {
+        yield count_longer(words, &len).await;
+    }
+}
+
+// paused, so the sleeps fire in order however loaded the machine is
+#[tokio::test(start_paused = true)]
+async fn select() {
+    let merged = merge_ticks(3, 2).shim().await;
+    assert_eq!(merged, vec![0, 100, 1, 101, 2]);
+
+    let received = recv_until(Duration::from_millis(250)).shim().await;
+    assert_eq!(received, 2);
+}
+
+#[effect(yields)]
+async fn periodic_ticks(n: u32, period: Duration) -> u32 {
+    for i in 0..n {
+        tokio::time::sleep(period).await;
+        yield i;
+    }
+}
+
+#[effect]
+async fn merge_ticks(a: u32, b: u32) -> Vec<u32> {
+    // offset, so the streams strictly alternate
+    let mut a = pin!(periodic_ticks(a, Duration::from_millis(20)));
+    let mut b = pin!(periodic_ticks(b, Duration::from_millis(25)));
+    let (mut a_done, mut b_done) = (false, false);
+    let mut merged = vec![];
+    loop {
+        jenner::select! {
+            x = a.as_mut(), if !a_done => match x {
+                Some(x) => merged.push(x),
+                None => a_done = true,
+            }
+            x = b.as_mut(), if !b_done => match x {
+                Some(x) => merged.push(100 + x),
+                None => b_done = true,
+            }
+            else => break merged,
+        }
+    }
+}
+
+#[effect]
+async fn recv_until(deadline: Duration) -> u32 {
+    let mut messages = pin!(periodic_ticks(10, Duration::from_millis(100)));
+    let mut deadline = pin!(tokio::time::sleep(deadline));
+    let mut received = 0;
+    loop {
+        jenner::select! {
+            biased;
+            () = deadline.as_mut() => break received,
+            Some(_) = messages.as_mut() => received += 1,
+        }
+    }
+}
+
+#[tokio::test]
+async fn select_fairness() {
+    let (first, second) = ready_picks(4).shim().await;
+    // each select rotates on its own, even with another one in the same loop
+    assert!(first.contains(&0) && first.contains(&1));
+    assert!(second.contains(&0) && second.contains(&1));
+}
+
+#[effect]
+async fn ready_picks(n: u32) -> (Vec<u32>, Vec<u32>) {
+    let (mut first, mut second) = (vec![], vec![]);
+    for _ in 0..n {
+        jenner::select! {
+            x = std::future::ready(0) => first.push(x),
+            x = std::future::ready(1) => first.push(x),
+        }
+        jenner::select! {
+            x = std::future::ready(0) => second.push(x),
+            x = std::future::ready(1) => second.push(x),
+        }
+    }
+    (first, second)
+}
+
+#[tokio::test(start_paused = true)]
+async fn join() {
+    // paused time makes the timing exact rather than dependent on load
+    let start = tokio::time::Instant::now();
+    assert_eq!(fetch_pair().shim().await, (10, 20));
+    // both fetches ran concurrently
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    let start = tokio::time::Instant::now();
+    assert_eq!(try_fetch_pair(1, 5).shim().await, Err("not found"));
+    // the slow fetch was dropped after the failure
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    assert_eq!(try_fetch_pair(2, 3).shim().await, Ok((20, 30)));
+}
+
+#[effect]
+async fn fetch_pair() -> (u32, u32) {
+    jenner::join!(fetch(1), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        20
+    })
+    .await
+}
+
+#[effect]
+async fn fetch(id: u32) -> u32 {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    id * 10
+}
+
+#[effect(fallible)]
+async fn try_fetch(id: u32) -> Result<u32, &'static str> {
+    tokio::time::sleep(Duration::from_millis(100 * id as u64)).await;
+    if id == 1 {
+        return Err("not found");
+    }
+    id * 10
+}
+
+#[effect(fallible)]
+async fn try_fetch_pair(a: u32, b: u32) -> Result<(u32, u32), &'static str> {
+    jenner::try_join!(try_fetch(a), try_fetch(b)).await
+}
+
+#[tokio::test]
+async fn nested_closures() {
+    assert_eq!(sum_parsed(vec!["1", "2", "x"]).shim().await, Ok(8));
+    assert_eq!(collect(tens()).shim().await, [10, 20]);
+}
+
+#[effect(fallible)]
+async fn sum_parsed(lines: Vec<&'static str>) -> Result<u32, std::num::ParseIntError> {
+    // the `?` returns from the closure, not from the effect
+    let parse = |line: &str| -> Option<u32> { Some(line.parse::<u32>().ok()? * 2) };
+    // the `.await` is a plain future await inside the async block
+    let total = async {
+        std::future::ready(()).await;
+        lines.iter().map(|line| parse(line).unwrap_or(0)).sum::<u32>()
+    };
+    total.await + "2".parse::<u32>()?
+}
+
+#[effect(yields)]
+async fn tens() -> u32 {
+    // the generator closure keeps its own yields
+    let mut inner = || {
+        yield 1;
+        yield 2;
+    };
+    while let std::ops::GeneratorState::Yielded(x) =
+        std::ops::Generator::resume(Pin::new(&mut inner), ())
+    {
+        yield x * 10;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn scope() {
+    // paused time makes the timing exact rather than dependent on load
+    let start = tokio::time::Instant::now();
+    assert_eq!(prefetched(2).shim().await, vec![0, 1, 2, 3, 4, 5]);
+    // the second page was fetched while the first was processed
+    assert!(start.elapsed() < Duration::from_millis(450));
+
+    let fetched = std::cell::Cell::new(0);
+    assert_eq!(scoped_fetches(&fetched).shim().await, Err("not found"));
+    // the detached fetch was joined before the scope exited
+    assert_eq!(fetched.get(), 1);
+}
+
+#[effect]
+async fn fetch_page(n: u32) -> Vec<u32> {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    (n * 3..n * 3 + 3).collect()
+}
+
+#[effect(yields)]
+async fn prefetch_pages(pages: u32) -> u32 {
+    jenner::scope!(|s| {
+        let mut next = Some(s.spawn(fetch_page(0)));
+        let mut n = 0;
+        while let Some(page) = next.take() {
+            let page = page.await;
+            n += 1;
+            if n < pages {
+                next = Some(s.spawn(fetch_page(n)));
+            }
+            for item in page {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                yield item;
+            }
+        }
+    })
+}
+
+#[effect]
+async fn prefetched(pages: u32) -> Vec<u32> {
+    let mut items = vec![];
+    #[effect(async)]
+    for item in prefetch_pages(pages) {
+        items.push(item);
+    }
+    items
+}
+
+#[effect(fallible)]
+async fn scoped_fetches(fetched: &std::cell::Cell<u32>) -> Result<u32, &'static str> {
+    let total = jenner::scope!(|s| {
+        let _ = s.spawn(future(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            fetched.set(fetched.get() + 1);
+        }));
+        let a = s.spawn(try_fetch(2));
+        let b = s.spawn(try_fetch(3));
+        a.await + b.await
+    });
+    let missing = jenner::scope!(|s| s.spawn(try_fetch(1)).await);
+    total + missing
+}
+
+#[tokio::test(start_paused = true)]
+async fn scope_exits() {
+    let joined = std::cell::Cell::new(0);
+    assert_eq!(exit_scopes(&joined).shim().await, Err("parse error"));
+    // the children were joined on `continue`, `break` and `?`
+    assert_eq!(joined.get(), 3);
+}
+
+#[effect(fallible)]
+async fn exit_scopes(joined: &std::cell::Cell<u32>) -> Result<u32, &'static str> {
+    let child = || async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        joined.set(joined.get() + 1);
+    };
+    for i in 0.. {
+        jenner::scope!(|s| {
+            let _ = s.spawn(future(child()));
+            if i == 0 {
+                continue;
+            }
+            break;
+        });
+    }
+    jenner::scope!(|s| {
+        let _ = s.spawn(future(child()));
+        "x".parse::<u32>().map_err(|_| "parse error")?
+    })
+}
+
+#[tokio::test]
+async fn scope_yields() {
+    let polls = std::cell::Cell::new(0);
+    // the child is polled each time the body is resumed after a yield
+    assert_eq!(collect(counting(&polls)).shim().await, vec![0, 1, 2]);
+    assert_eq!(polls.get(), 10);
+}
+
+/// Counts its polls, completing on the tenth
+struct Polls<'a>(&'a std::cell::Cell<u32>);
+
+impl std::future::Future for Polls<'_> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+        self.0.set(self.0.get() + 1);
+        if self.0.get() == 10 {
+            std::task::Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    }
+}
+
+#[effect(yields)]
+async fn counting(polls: &std::cell::Cell<u32>) -> u32 {
+    jenner::scope!(|s| {
+        let _ = s.spawn(future(Polls(polls)));
+        for _ in 0..3 {
+            yield polls.get();
+        }
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn concurrent() {
+    // paused time makes the timing exact rather than dependent on load
+    let start = tokio::time::Instant::now();
+    let delays = vec![300, 100, 200];
+    assert_eq!(collect(delayed(delays.clone())).shim().await, vec![300, 100, 200]);
+    // the bodies ran at the same time
+    assert!(start.elapsed() < Duration::from_millis(400));
+
+    assert_eq!(
+        collect(delayed_unordered(delays)).shim().await,
+        vec![100, 200, 300]
+    );
+
+    let started = std::cell::Cell::new(0);
+    assert_eq!(collect(until_large(&started)).shim().await, vec![1, 2]);
+    // bodies 3 and 4 started as 1 and 2 finished, and no more after 3 broke
+    assert_eq!(started.get(), 4);
+
+    // the items yielded before the break are passed on, up to the body that broke
+    assert_eq!(collect(flush_before_break()).shim().await, vec![10, 20]);
+}
+
+#[effect(yields)]
+async fn delayed(delays: Vec<u32>) -> u32 {
+    #[effect(async, concurrent(3))]
+    for delay in delays {
+        tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+        yield delay;
+    }
+}
+
+#[effect(yields)]
+async fn delayed_unordered(delays: Vec<u32>) -> u32 {
+    #[effect(async, concurrent(3), unordered)]
+    for delay in delays {
+        tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+        yield delay;
+    }
+}
+
+#[effect(yields)]
+async fn until_large(started: &std::cell::Cell<u32>) -> u32 {
+    #[effect(async, concurrent(2))]
+    for i in 1..100 {
+        started.set(started.get() + 1);
+        tokio::time::sleep(Duration::from_millis(10 * i as u64)).await;
+        if i == 3 {
+            break;
+        }
+        yield i;
+    }
+}
+
+#[effect(yields)]
+async fn flush_before_break() -> u32 {
+    #[effect(async, concurrent(4))]
+    for i in 0..4 {
+        match i {
+            // still running when the loop breaks
+            0 => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                yield 0;
+            }
+            2 => {
+                yield 20;
+                tokio::task::yield_now().await;
+                break;
+            }
+            // comes after the break
+            3 => yield 30,
+            i => yield i * 10,
+        }
+    }
+}
+
+#[tokio::test]
+async fn share() {
+    let a = jenner::share_with(countdown(), 2, jenner::lag::Block);
+    let b = a.clone();
+    // both consumers see every item, whichever one polls the countdown
+    let (a, b) = tokio::join!(collect(a).shim(), collect(b).shim());
+    assert_eq!(a, vec![5, 4, 3, 2, 1, 0]);
+    assert_eq!(b, vec![5, 4, 3, 2, 1, 0]);
+
+    let fast = jenner::share_with(countdown(), 2, jenner::lag::DropOldest);
+    let slow = fast.clone();
+    assert_eq!(collect(fast).shim().await, vec![5, 4, 3, 2, 1, 0]);
+    // only the last two items were still buffered
+    assert_eq!(collect(slow).shim().await, vec![1, 0]);
+
+    let mut fast = pin!(jenner::share_with(countdown(), 2, jenner::lag::Fail));
+    let mut slow = pin!(fast.clone());
+    while jenner::try_next(&mut fast).shim().await.unwrap().is_some() {}
+    assert_eq!(
+        jenner::try_next(&mut slow).shim().await,
+        Err(jenner::Lagged(4))
+    );
+    assert_eq!(jenner::try_next(&mut slow).shim().await, Ok(Some(1)));
+
+    // every consumer reports the failure
+    let a = jenner::share(checked(countdown()));
+    let b = a.clone();
+    let (a, b) = tokio::join!(collect_checked(a).shim(), collect_checked(b).shim());
+    assert_eq!(a, (vec![1, 5, 4, 3], Some("too small")));
+    assert_eq!(b, (vec![1, 5, 4, 3], Some("too small")));
+}
+
+#[tokio::test]
+async fn budget() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let counter = ticks.clone();
+    let ticker = tokio::spawn(async move {
+        loop {
+            counter.fetch_add(1, Ordering::Relaxed);
+            tokio::task::yield_now().await;
+        }
+    });
+
+    let v = collect(budgeted(100)).shim().await;
+    assert_eq!(v, (0..100).collect::<Vec<_>>());
+    // the stream is always ready, but the budget let the ticker run
+    assert!(ticks.load(Ordering::Relaxed) > 0);
+
+    let before = ticks.load(Ordering::Relaxed);
+    assert_eq!(yields_now(4).shim().await, 4);
+    assert!(ticks.load(Ordering::Relaxed) > before);
+    ticker.abort();
+}
+
+#[effect(yields, budget = 32)]
+async fn budgeted(n: u32) -> u32 {
+    #[effect(async)]
+    for i in futures::stream::iter(0..n) {
+        yield i;
+    }
+}
+
+#[effect]
+async fn yields_now(n: u32) -> u32 {
+    for _ in 0..n {
+        jenner::yield_now().await;
+    }
+    n
+}
+
+#[test]
+fn block_on() {
+    assert_eq!(jenner::block_on(yields_now(3)), 3);
+
+    // tokio's timers need a runtime to fire, but not an async caller
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+    assert_eq!(jenner::try_block_on(try_fetch_pair(2, 3)), Ok((20, 30)));
+    assert_eq!(jenner::try_block_on(try_fetch(1)), Err("not found"));
+
+    let v: Vec<_> = jenner::block_iter(countdown()).collect();
+    assert_eq!(v, vec![5, 4, 3, 2, 1, 0]);
+    let v: Vec<_> = jenner::try_block_iter(checked(countdown())).collect();
+    assert_eq!(v, vec![Ok(1), Ok(5), Ok(4), Ok(3), Err("too small")]);
+}
+
+#[tokio::test]
+async fn offload() {
+    let v = collect(jenner::offload(countdown_from(5), 2)).shim().await;
+    assert_eq!(v, vec![5, 4, 3, 2, 1]);
+
+    let mut lines = pin!(jenner::offload(parse_lines(vec!["1", "2", "x"]), 1));
+    assert_eq!(jenner::try_next(&mut lines).shim().await, Ok(Some(1)));
+    assert_eq!(jenner::try_next(&mut lines).shim().await, Ok(Some(2)));
+    assert!(jenner::try_next(&mut lines).shim().await.is_err());
+
+    let (pump, items) = jenner::pump(countdown(), 1);
+    let consumer = std::thread::spawn(move || items.collect::<Vec<_>>());
+    pump.shim().await;
+    assert_eq!(consumer.join().unwrap(), vec![5, 4, 3, 2, 1, 0]);
+
+    let (pump, items) = jenner::pump(checked(countdown()), 1);
+    let consumer = std::thread::spawn(move || items.collect::<Vec<_>>());
+    pump.shim().await;
+    assert_eq!(
+        consumer.join().unwrap(),
+        vec![Ok(1), Ok(5), Ok(4), Ok(3), Err("too small")]
+    );
+
+    // the iterator ends early when the pump is dropped
+    let (pump, mut items) = jenner::pump(countdown(), 1);
+    drop(pump);
+    assert_eq!(items.next(), None);
+}
+
+#[effect(yields, fallible)]
+fn parse_lines(lines: Vec<&'static str>) -> Result<u32, std::num::ParseIntError> {
+    for line in lines {
+        yield line.parse::<u32>()?;
+    }
+}
+
+#[test]
+fn coop() {
+    use jenner::coop::{Scheduler, Signal};
+    use std::cell::RefCell;
+
+    let log = RefCell::new(vec![]);
+    let (ping, pong, never) = (Signal::new(), Signal::new(), Signal::new());
+    let mut scheduler = Scheduler::new();
+    scheduler.spawn(player("ping", &ping, &pong, &log));
+    scheduler.spawn(player("pong", &pong, &ping, &log));
+    scheduler.spawn(serve(&ping));
+    scheduler.spawn(wait_for(&never));
+
+    // the ball is served on tick 2, and each player takes 3 ticks to return it
+    scheduler.run_until(4);
+    assert_eq!(*log.borrow(), ["ping"]);
+    scheduler.run_until(10);
+    assert_eq!(scheduler.now(), 10);
+    assert_eq!(*log.borrow(), ["ping", "pong", "ping"]);
+
+    scheduler.run();
+    assert_eq!(*log.borrow(), ["ping", "pong", "ping", "pong"]);
+    assert_eq!(scheduler.now(), 15);
+    // the last task is left waiting for a signal that nothing can fire
+    assert_eq!(scheduler.len(), 1);
+
+    // sleeping past the end of the clock doesn't overflow it
+    let mut scheduler = Scheduler::new();
+    scheduler.spawn(oversleep());
+    scheduler.run();
+    assert_eq!(scheduler.now(), u64::MAX);
+    assert_eq!(scheduler.len(), 1);
+}
+
+#[effect(yields)]
+fn serve(ball: &jenner::coop::Signal) -> jenner::coop::Request {
+    yield jenner::coop::Request::Sleep(2);
+    ball.fire();
+}
+
+#[effect(yields)]
+fn player<'a>(
+    name: &'static str,
+    turn: &'a jenner::coop::Signal,
+    other: &'a jenner::coop::Signal,
+    log: &'a std::cell::RefCell<Vec<&'static str>>,
+) -> jenner::coop::Request {
+    for _ in 0..2 {
+        yield jenner::coop::Request::WaitFor(turn.clone());
+        log.borrow_mut().push(name);
+        yield jenner::coop::Request::Sleep(2);
+        other.fire();
+    }
+}
+
+#[effect(yields)]
+fn wait_for(signal: &jenner::coop::Signal) -> jenner::coop::Request {
+    yield jenner::coop::Request::WaitFor(signal.clone());
+}
+
+#[effect(yields)]
+fn oversleep() -> jenner::coop::Request {
+    yield jenner::coop::Request::Sleep(1);
+    yield jenner::coop::Request::Sleep(u64::MAX);
+}
+
+#[effect]
+fn answer() -> u32 {
+    42
+}
+
+#[jenner::test]
+fn entry_sync() {
+    let x = answer().await;
+    assert_eq!(x, 42);
+}
+
+#[jenner::test(timeout = 1000)]
+async fn entry_async() {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(10));
+        tx.send(21).unwrap();
+    });
+    let x = rx.await.unwrap();
+    assert_eq!(x * 2, 42);
+}
+
+#[jenner::test(fallible)]
+async fn entry_fallible() -> Result<(), std::num::ParseIntError> {
+    jenner::yield_now().await;
+    let x = "42".parse::<u32>()?;
+    assert_eq!(x, 42);
+}
+
+#[jenner::test(fallible)]
+#[should_panic(expected = "the effect failed: ParseIntError")]
+async fn entry_failure() -> Result<(), std::num::ParseIntError> {
+    "nope".parse::<u32>()?;
+}
+
+#[jenner::test(timeout = 50)]
+#[should_panic(expected = "the effect timed out after 50ms")]
+async fn entry_timeout() {
+    let (_tx, rx) = futures::channel::oneshot::channel::<()>();
+    rx.await.unwrap();
+}